@@ -8,27 +8,79 @@ pub(crate) struct Args {
     pub command: Commands,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Default)]
 pub(crate) enum CompilationType {
     Ast,
+    Ir,
+    #[default]
     Mips,
 }
 
-impl Default for CompilationType {
-    fn default() -> Self {
-        Self::Mips
-    }
-}
-
 impl std::fmt::Display for CompilationType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             CompilationType::Ast => write!(f, "ast"),
+            CompilationType::Ir => write!(f, "ir"),
             CompilationType::Mips => write!(f, "mips"),
         }
     }
 }
 
+/// What the compiled program does once `main` returns.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Default)]
+pub(crate) enum Termination {
+    #[default]
+    Halt,
+    Restart,
+}
+
+impl std::fmt::Display for Termination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Termination::Halt => write!(f, "halt"),
+            Termination::Restart => write!(f, "restart"),
+        }
+    }
+}
+
+impl From<Termination> for ayysee_compiler::Termination {
+    fn from(termination: Termination) -> Self {
+        match termination {
+            Termination::Halt => Self::Halt,
+            Termination::Restart => Self::Restart,
+        }
+    }
+}
+
+/// How doc comments appear in the compiled program.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug, Default)]
+pub(crate) enum DocComments {
+    #[default]
+    Omit,
+    Lines,
+    Trailing,
+}
+
+impl std::fmt::Display for DocComments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocComments::Omit => write!(f, "omit"),
+            DocComments::Lines => write!(f, "lines"),
+            DocComments::Trailing => write!(f, "trailing"),
+        }
+    }
+}
+
+impl From<DocComments> for ayysee_compiler::DocComments {
+    fn from(doc_comments: DocComments) -> Self {
+        match doc_comments {
+            DocComments::Omit => Self::Omit,
+            DocComments::Lines => Self::Lines,
+            DocComments::Trailing => Self::Trailing,
+        }
+    }
+}
+
 #[derive(clap::Subcommand, Debug)]
 pub(crate) enum Commands {
     /// Invoke the ayysee compiler
@@ -38,5 +90,17 @@ pub(crate) enum Commands {
         /// Select what type of output to generate
         #[clap(short, long, value_enum, default_value_t = CompilationType::default())]
         output: CompilationType,
+        /// Select what the program does once `main` returns
+        #[clap(long, value_enum, default_value_t = Termination::default())]
+        termination: Termination,
+        /// Warn about comparisons between temperatures and pressures
+        #[clap(long)]
+        lint_units: bool,
+        /// Select how `///` comments on functions and aliases are emitted
+        #[clap(long, value_enum, default_value_t = DocComments::default())]
+        doc_comments: DocComments,
+        /// Jump to labels by name instead of by line number
+        #[clap(long)]
+        keep_labels: bool,
     },
 }
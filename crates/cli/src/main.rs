@@ -1,5 +1,5 @@
 use crate::{commands::Commands, error::Result};
-use ayysee_compiler::generate_program;
+use ayysee_compiler::{generate_ir, generate_program_with_options, Options};
 use ayysee_parser::grammar::ProgramParser;
 use clap::Parser;
 
@@ -12,17 +12,35 @@ async fn main() -> Result<()> {
 
     let args = commands::Args::parse();
     match args.command {
-        Commands::Compile { file, output } => {
+        Commands::Compile {
+            file,
+            output,
+            termination,
+            lint_units,
+            doc_comments,
+            keep_labels,
+        } => {
             let file_contents = tokio::fs::read_to_string(file).await.unwrap();
 
             let parser = ProgramParser::new();
 
             let parsed = parser.parse(&file_contents).unwrap();
 
+            let options = Options {
+                termination: termination.into(),
+                lint_units,
+                doc_comments: doc_comments.into(),
+                keep_labels,
+            };
+
             match output {
                 commands::CompilationType::Ast => println!("{:#?}", parsed),
+                commands::CompilationType::Ir => {
+                    let ir = generate_ir(parsed, &options)?;
+                    println!("{}", ir);
+                }
                 commands::CompilationType::Mips => {
-                    let compiled = generate_program(parsed)?;
+                    let compiled = generate_program_with_options(parsed, &options)?;
                     println!("{}", compiled);
                 }
             }
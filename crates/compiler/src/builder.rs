@@ -0,0 +1,285 @@
+use ayysee_ir::{BasicBlock, BlockId, Function, Op, Terminator, VReg};
+use ayysee_parser::ast::Identifier;
+use std::collections::HashMap;
+
+/// The name of the function that holds the top-level initialisation. Like the generated labels,
+/// it contains a `.`, which identifiers cannot, so it never clashes with a user function.
+pub(crate) const TOP_LEVEL: &str = "top.level";
+
+/// A loop that code is currently being lowered for.
+pub(crate) struct ActiveLoop {
+    /// The label given to the loop in the source, if any
+    label: Option<String>,
+    /// The block that `continue` jumps to
+    pub(crate) continue_block: BlockId,
+    /// The block that `break` jumps to
+    pub(crate) break_block: BlockId,
+}
+
+/// Builds the IR of one function at a time.
+///
+/// Operations are appended to the current block. Once a block has been terminated, operations
+/// that follow can never run, so they are collected in a fresh block that nothing jumps to.
+pub(crate) struct Builder {
+    function: Function,
+    /// The block that operations are appended to, unless it has been terminated
+    current: Option<BlockId>,
+    /// The blocks in the order they were started, which is the order they are laid out in
+    order: Vec<BlockId>,
+
+    /// Locals of the current function and the registers that hold them
+    pub(crate) locals: HashMap<String, VReg>,
+    /// Whether the current function is a real function, as `return` is not allowed at the top
+    /// level
+    pub(crate) in_function: bool,
+    /// Keeps track of the loops that are currently active.
+    loops: Vec<ActiveLoop>,
+
+    loop_counter: i32,
+    if_counter: i32,
+    match_counter: i32,
+    logic_counter: i32,
+    unreachable_counter: i32,
+}
+
+impl Builder {
+    pub(crate) fn new() -> Self {
+        Self {
+            function: Function::new(String::new()),
+            current: None,
+            order: Vec::new(),
+            locals: HashMap::new(),
+            in_function: false,
+            loops: Vec::new(),
+            loop_counter: 0,
+            if_counter: 0,
+            match_counter: 0,
+            logic_counter: 0,
+            unreachable_counter: 0,
+        }
+    }
+
+    /// Starts building a new function, forgetting about the locals of the previous one.
+    /// The entry block is named after the function.
+    pub(crate) fn start_function(&mut self, name: String, in_function: bool) {
+        self.function = Function::new(name.clone());
+        self.order.clear();
+        self.locals.clear();
+        self.loops.clear();
+        self.in_function = in_function;
+        self.loop_counter = 0;
+        self.if_counter = 0;
+        self.match_counter = 0;
+        self.logic_counter = 0;
+        self.unreachable_counter = 0;
+
+        let entry = self.new_block(name);
+        self.switch_to(entry);
+    }
+
+    /// Finishes the current function and returns it.
+    /// A function that runs off the end of its body returns without a value.
+    pub(crate) fn finish_function(&mut self) -> Function {
+        self.terminate(Terminator::Return(None));
+
+        // lay the blocks out in the order they were started in, so that the code of a statement
+        // follows the code before it
+        let mut order = std::mem::take(&mut self.order);
+        order.extend(
+            self.function
+                .block_ids()
+                .filter(|block| !order.contains(block))
+                .collect::<Vec<_>>(),
+        );
+        let mut position = vec![BlockId(0); order.len()];
+        for (new, old) in order.iter().enumerate() {
+            position[old.0] = BlockId(new);
+        }
+
+        let mut function = std::mem::replace(&mut self.function, Function::new(String::new()));
+        let mut blocks: Vec<Option<BasicBlock>> = function.blocks.drain(..).map(Some).collect();
+        function.blocks = order
+            .iter()
+            .map(|old| {
+                let mut block = blocks[old.0].take().expect("every block is laid out once");
+                block.terminator.map_successors(|target| position[target.0]);
+                block
+            })
+            .collect();
+
+        function
+    }
+
+    /// The function that is being built.
+    pub(crate) fn function(&mut self) -> &mut Function {
+        &mut self.function
+    }
+
+    /// Creates a new virtual register.
+    pub(crate) fn new_register(&mut self) -> VReg {
+        self.function.new_register()
+    }
+
+    /// Creates a block that is entered once code has been lowered into it with `switch_to`.
+    pub(crate) fn new_block(&mut self, name: String) -> BlockId {
+        self.function.blocks.push(BasicBlock::new(name));
+        BlockId(self.function.blocks.len() - 1)
+    }
+
+    /// Continues lowering into a block.
+    /// If the current block has not been terminated, it falls through to the new one.
+    pub(crate) fn switch_to(&mut self, block: BlockId) {
+        self.terminate(Terminator::Jump(block));
+        self.order.push(block);
+        self.current = Some(block);
+    }
+
+    /// Appends an operation to the current block.
+    pub(crate) fn push(&mut self, op: Op) {
+        let block = match self.current {
+            Some(block) => block,
+            None => {
+                let name = self.label_name("unreachable", self.unreachable_counter);
+                self.unreachable_counter += 1;
+                let block = self.new_block(name);
+                self.order.push(block);
+                self.current = Some(block);
+                block
+            }
+        };
+
+        self.function.block_mut(block).ops.push(op);
+    }
+
+    /// Ends the current block. Nothing happens if it has already been terminated.
+    pub(crate) fn terminate(&mut self, terminator: Terminator) {
+        if let Some(block) = self.current.take() {
+            self.function.block_mut(block).terminator = terminator;
+        }
+    }
+
+    /// Names the blocks of a statement, e.g. `main.loop_0`.
+    /// Blocks become labels, which share one namespace with the functions of the program. The
+    /// name of the function keeps them apart from the labels of other functions, and the `.`
+    /// keeps them apart from the functions themselves.
+    fn label_name(&self, kind: &str, counter: i32) -> String {
+        format!("{}.{kind}_{counter}", self.function.name)
+    }
+
+    /// Marks the beginning of a loop.
+    /// The returned name is used for the blocks of the loop.
+    pub(crate) fn new_loop(&mut self) -> String {
+        let name = self.label_name("loop", self.loop_counter);
+        self.loop_counter += 1;
+
+        name
+    }
+
+    /// Makes `break` and `continue` refer to a loop until `end_loop` is called.
+    pub(crate) fn start_loop(
+        &mut self,
+        label: Option<&Identifier>,
+        continue_block: BlockId,
+        break_block: BlockId,
+    ) {
+        self.loops.push(ActiveLoop {
+            label: label.map(|label| label.to_string()),
+            continue_block,
+            break_block,
+        });
+    }
+
+    /// Finds the loop that `break` or `continue` refers to.
+    /// Without a label this is the innermost loop.
+    pub(crate) fn find_loop(&self, label: Option<&Identifier>) -> Option<&ActiveLoop> {
+        match label {
+            Some(label) => {
+                let label: &str = label.as_ref();
+                self.loops
+                    .iter()
+                    .rev()
+                    .find(|active| active.label.as_deref() == Some(label))
+            }
+            None => self.loops.last(),
+        }
+    }
+
+    /// Marks the end of a loop.
+    pub(crate) fn end_loop(&mut self) {
+        self.loops.pop();
+    }
+
+    pub(crate) fn new_if(&mut self) -> String {
+        let name = self.label_name("if", self.if_counter);
+        self.if_counter += 1;
+
+        name
+    }
+
+    pub(crate) fn new_match(&mut self) -> String {
+        let name = self.label_name("match", self.match_counter);
+        self.match_counter += 1;
+
+        name
+    }
+
+    /// Creates a unique name for a short-circuiting logical operator.
+    pub(crate) fn new_logic(&mut self, operator: &str) -> String {
+        let name = self.label_name(operator, self.logic_counter);
+        self.logic_counter += 1;
+
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::compile;
+
+    #[test]
+    fn test_labels_do_not_clash_with_functions() {
+        // the blocks of every function, including the top level, are named after it, and
+        // functions can be named like any of them
+        let program = compile(
+            "let x = d0.Setting;
+            if x > 0 { d1.On = 1; }
+            fn init(x) {
+                let i = 0;
+                loop { i += 1; if i == x { return i; } }
+            }
+            fn loop_0() { return 1; }
+            fn main_loop_0() { return 2; }
+            fn main() {
+                let total = 0;
+                loop {
+                    total += init(2) + loop_0() + main_loop_0();
+                    if total > 5 { break; }
+                }
+                d0.Setting = total;
+            }",
+        );
+        let labels: Vec<&String> = program
+            .lines
+            .iter()
+            .filter(|line| line.ends_with(':'))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "top.level.if_0_end:",
+                "init:",
+                "init.loop_0:",
+                "init.if_0_end:",
+                "loop_0:",
+                "main_loop_0:",
+                "main:",
+                "main.loop_0:",
+                "main.if_0_end:",
+                "main.loop_0_end:",
+            ]
+        );
+
+        let writes = program.writes(&[("d0 Setting", 1.0)]);
+        assert_eq!(writes, ["d1 On 1", "d0 Setting 10"]);
+    }
+}
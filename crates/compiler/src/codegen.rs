@@ -1,10 +1,26 @@
-use crate::error::Result;
+use crate::{
+    error::{Error, Result},
+    peephole, DocComments,
+};
 use ayysee_parser::ast::{Identifier, Value};
 use stationeers_mips::{
     instructions::{Instruction, Misc},
     types::Device,
 };
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+/// The information about a function that is needed to call it.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct FunctionSignature {
+    /// The number of parameters the function takes
+    pub(crate) parameters: usize,
+    /// Whether the function returns a value with `return <expr>;`
+    pub(crate) returns_value: bool,
+}
 
 pub(crate) struct CodeGenerator {
     /// the instructions that have been generated
@@ -12,14 +28,23 @@ pub(crate) struct CodeGenerator {
     /// comments that have been added to specific instruction lines
     comments: HashMap<i32, String>,
 
-    /// 'labels' that have been added to the code to mark a specific line of code for jumping to
-    pub(crate) labels: HashMap<String, i32>,
-
     /// Device aliases mapped to their actual device
     devices: HashMap<Identifier, Device>,
 
     /// Constants that have been defined
     constants: HashMap<Identifier, Value>,
+    /// Constants that have been looked up, so that unused ones can be reported.
+    /// Constants are looked up while evaluating expressions, which only borrows the generator.
+    used_constants: RefCell<HashSet<Identifier>>,
+
+    /// Functions that have been declared
+    functions: HashMap<Identifier, FunctionSignature>,
+
+    /// Whether to warn about comparisons between mismatched units
+    pub(crate) lint_units: bool,
+
+    /// How doc comments are emitted
+    pub(crate) doc_comments: DocComments,
 }
 
 impl CodeGenerator {
@@ -27,9 +52,12 @@ impl CodeGenerator {
         Self {
             instructions: Vec::new(),
             comments: HashMap::new(),
-            labels: HashMap::new(),
             devices: HashMap::new(),
             constants: HashMap::new(),
+            used_constants: RefCell::new(HashSet::new()),
+            functions: HashMap::new(),
+            lint_units: false,
+            doc_comments: DocComments::default(),
         }
     }
 
@@ -56,35 +84,55 @@ impl CodeGenerator {
         self.add_instruction(Instruction::from(Misc::Comment { comment }));
     }
 
-    /// Creates a new label and adds it to the list of labels.
-    pub(crate) fn add_label(&mut self, label: String) {
-        // implementation that inserts a label instruction:
-        self.add_instruction(Instruction::from(Misc::Label {
-            name: label.clone(),
-        }));
-        self.labels.insert(label, self.instructions.len() as i32);
+    /// Adds the doc comments of an item on separate lines, if they are emitted that way.
+    /// This should be called before the item's instruction is added.
+    pub(crate) fn add_doc_lines(&mut self, docs: &[String]) {
+        if let DocComments::Lines = self.doc_comments {
+            for doc in docs {
+                self.add_comment_line(doc.clone());
+            }
+        }
     }
 
-    /// Checks if a label exists.
-    pub(crate) fn has_label(&self, label: &str) -> bool {
-        self.labels.contains_key(label)
+    /// Appends the doc comments of an item to the comment of the last instruction, if they are
+    /// emitted that way.
+    pub(crate) fn add_doc_trailing(&mut self, docs: &[String]) {
+        if docs.is_empty() || !matches!(self.doc_comments, DocComments::Trailing) {
+            return;
+        }
+
+        let docs = docs.join(" ");
+        let line = self.instructions.len() as i32 - 1;
+        let comment = match self.comments.remove(&line) {
+            Some(comment) => format!("{comment} {docs}"),
+            None => docs,
+        };
+        self.insert_comment(comment, line);
     }
 
-    /// Gets the address of a label.
-    /// This should only be called after a pass has been completed to ensure that
-    /// the label exists.
-    pub(crate) fn get_label(&self, label: &str) -> Result<i32> {
-        self.labels
-            .get(label)
-            .copied()
-            .ok_or_else(|| unreachable!("label {} does not exist", label))
+    /// Adds a label that marks the next instruction as a target for jumps.
+    /// Jumps refer to the label by name until the program is relocated.
+    pub(crate) fn add_label(&mut self, label: String) {
+        self.add_instruction(Instruction::from(Misc::Label { name: label }));
     }
 
-    /// Clears out data from the first pass.
-    /// This should be called before the second pass.
-    pub(crate) fn clear_first_pass(&mut self) {
-        self.comments.clear();
-        self.instructions.clear();
+    /// Runs the peephole optimiser over the instructions.
+    /// Comments are moved along with the lines they belong to, and comments of removed lines are
+    /// dropped.
+    pub(crate) fn optimize(&mut self) {
+        let lines = peephole::optimize(&mut self.instructions);
+        let new_line = |line: i32| {
+            usize::try_from(line)
+                .ok()
+                .and_then(|line| lines.get(line).copied().flatten())
+                .map(|line| line as i32)
+        };
+
+        self.comments = self
+            .comments
+            .drain()
+            .filter_map(|(line, comment)| Some((new_line(line)?, comment)))
+            .collect();
     }
 
     // TODO: Rewrites usages of identifiers to refer to the device.
@@ -125,13 +173,120 @@ impl CodeGenerator {
         Ok(self.devices.get(identifier).copied())
     }
 
+    /// Resolves an identifier to a device.
+    /// The identifier can either be an alias or the name of a pin such as `d0`.
+    pub(crate) fn resolve_device(&self, identifier: &Identifier) -> Result<Device> {
+        if let Some(device) = self.get_device(identifier)? {
+            return Ok(device);
+        }
+
+        let identifier_ref: &str = identifier.as_ref();
+        Device::from_str(identifier_ref).map_err(|_| Error::UndefinedDevice(identifier.to_string()))
+    }
+
     /// Adds a constant to the list of constants.
     pub(crate) fn add_constant(&mut self, identifier: Identifier, value: Value) {
         self.constants.insert(identifier, value);
     }
 
+    /// Whether a constant is defined, without counting as a use of it.
+    pub(crate) fn is_constant(&self, identifier: &Identifier) -> bool {
+        self.constants.contains_key(identifier)
+    }
+
     /// Gets the value of a constant.
     pub(crate) fn get_constant(&self, identifier: &Identifier) -> Option<Value> {
-        self.constants.get(identifier).copied()
+        let value = self.constants.get(identifier).copied();
+        if value.is_some() {
+            self.used_constants.borrow_mut().insert(identifier.clone());
+        }
+        value
+    }
+
+    /// Gets the names of the constants that have never been looked up, in alphabetical order.
+    pub(crate) fn unused_constants(&self) -> Vec<String> {
+        let used = self.used_constants.borrow();
+        let mut unused: Vec<String> = self
+            .constants
+            .keys()
+            .filter(|identifier| !used.contains(*identifier))
+            .map(Identifier::to_string)
+            .collect();
+        unused.sort();
+        unused
+    }
+
+    /// Declares a function so that it can be called before its definition is generated.
+    pub(crate) fn add_function(&mut self, identifier: Identifier, signature: FunctionSignature) {
+        self.functions.insert(identifier, signature);
+    }
+
+    /// Gets the signature of a declared function.
+    pub(crate) fn get_function(&self, identifier: &Identifier) -> Option<FunctionSignature> {
+        self.functions.get(identifier).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::compile_with, DocComments, Options};
+
+    const SOURCE: &str = "/// The sensor
+        /// in the room
+        def d0 as Sensor;
+        /// Reads the sensor
+        fn sample() { return Sensor.Temperature; }
+        /// The entry point
+        fn main() { d1.Setting = sample(); }";
+
+    /// Compiles `SOURCE`, checking that the comments do not change what the program does.
+    fn compile_docs(doc_comments: DocComments) -> Vec<String> {
+        let options = Options {
+            doc_comments,
+            ..Options::default()
+        };
+        let program = compile_with(SOURCE, &options).unwrap();
+        assert_eq!(
+            program.writes(&[("d0 Temperature", 300.0)]),
+            ["d1 Setting 300"]
+        );
+        program.code.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let code = compile_docs(DocComments::Omit);
+        assert!(!code.iter().any(|line| line.contains('#')), "{code:?}");
+
+        // every comment gets a line of its own before what it documents, which moves the code
+        // after it
+        let code = compile_docs(DocComments::Lines);
+        assert_eq!(
+            code[..7],
+            [
+                "# The sensor",
+                "# in the room",
+                "alias Sensor d0",
+                "jal 12",
+                "hcf",
+                "# Reads the sensor",
+                "sample:",
+            ]
+        );
+        assert_eq!(code[10..12], ["# The entry point", "main:"]);
+        assert_eq!(code[13], "jal 7");
+
+        // the comments of an item are joined at the end of its line
+        let code = compile_docs(DocComments::Trailing);
+        assert_eq!(
+            code[..4],
+            [
+                "alias Sensor d0 # The sensor in the room",
+                "jal 8",
+                "hcf",
+                "sample: # Reads the sensor",
+            ]
+        );
+        assert_eq!(code[7], "main: # The entry point");
     }
 }
@@ -0,0 +1,276 @@
+use crate::{
+    codegen::CodeGenerator,
+    error::{Error, Result},
+    intrinsics::{check_argument_types, Intrinsic},
+};
+use ayysee_parser::ast::{BinaryOpcode, Dimension, Expr, UnaryOpcode, Value};
+use stationeers_mips::hash::hash;
+
+/// Evaluates an expression that must be known at compile time.
+///
+/// The result follows the semantics of the IC10 instructions the expression would otherwise
+/// compile to, e.g. `mod` is never negative. Integers are kept as integers as long as the result
+/// is exact, otherwise the result is a float. Quantities keep their unit through negation,
+/// addition and subtraction so that their dimension is still known.
+pub(crate) fn evaluate_constant(expr: &Expr, codegen: &CodeGenerator) -> Result<Value> {
+    let not_constant = || Error::NotConstant(format!("{expr:?}"));
+
+    let value = match expr {
+        Expr::Constant(value) => *value,
+        Expr::Hash(name) => Value::Integer(hash(name).into()),
+        Expr::Identifier(identifier) => codegen
+            .get_constant(identifier)
+            .ok_or_else(|| Error::NotConstant(identifier.to_string()))?,
+        Expr::UnaryOp(op, operand) => {
+            let operand = evaluate_constant(operand, codegen)?;
+            match op {
+                UnaryOpcode::Not => Value::Boolean(!is_truthy(operand)),
+                UnaryOpcode::Neg => match operand {
+                    Value::Integer(i) => i
+                        .checked_neg()
+                        .map(Value::Integer)
+                        .ok_or_else(not_constant)?,
+                    // `-20C` is a temperature below freezing, not the negated Kelvin value
+                    Value::Quantity(value, unit) => Value::Quantity(-value, unit),
+                    _ => Value::Float(-as_float(operand)),
+                },
+                UnaryOpcode::BitNot => Value::Integer(!as_integer(operand)),
+            }
+        }
+        Expr::BinaryOp(left, op, right) => {
+            let left = evaluate_constant(left, codegen)?;
+            let right = evaluate_constant(right, codegen)?;
+            evaluate_binary_op(left, *op, right).ok_or_else(not_constant)?
+        }
+        Expr::Select(condition, then, otherwise) => {
+            if is_truthy(evaluate_constant(condition, codegen)?) {
+                evaluate_constant(then, codegen)?
+            } else {
+                evaluate_constant(otherwise, codegen)?
+            }
+        }
+        Expr::Call(identifier, arguments) => {
+            let name: &str = identifier.as_ref();
+            let intrinsic = Intrinsic::from_name(name).ok_or_else(not_constant)?;
+            if intrinsic.arity() != arguments.len() {
+                return Err(not_constant());
+            }
+            check_argument_types(identifier, arguments, codegen)?;
+
+            let arguments = arguments
+                .iter()
+                .map(|argument| evaluate_constant(argument, codegen))
+                .collect::<Result<Vec<_>>>()?;
+            let integers = arguments
+                .iter()
+                .all(|argument| matches!(argument, Value::Integer(_)));
+            let arguments: Vec<f64> = arguments.into_iter().map(as_float).collect();
+
+            let result = intrinsic.evaluate(&arguments).ok_or_else(not_constant)?;
+            if intrinsic.is_integral() || (integers && intrinsic.preserves_integers()) {
+                Value::Integer(result as i64)
+            } else {
+                Value::Float(result)
+            }
+        }
+        _ => return Err(not_constant()),
+    };
+
+    // values that cannot be written as a number in IC10 are left for runtime
+    match value {
+        Value::Float(f) | Value::Quantity(f, _) if !f.is_finite() => Err(not_constant()),
+        value => Ok(value),
+    }
+}
+
+/// Applies a binary operator to two constants.
+/// Returns `None` if the result cannot be represented.
+fn evaluate_binary_op(left: Value, op: BinaryOpcode, right: Value) -> Option<Value> {
+    let integers = match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Some((a, b)),
+        _ => None,
+    };
+    let (a, b) = (as_float(left), as_float(right));
+    let float = |value: f64| match quantity_dimension(left, right) {
+        Some(dimension) => Value::Quantity(value, dimension.base_unit()),
+        None => Value::Float(value),
+    };
+
+    let value = match op {
+        BinaryOpcode::Add => match integers {
+            Some((x, y)) => Value::Integer(x.checked_add(y)?),
+            None => float(a + b),
+        },
+        BinaryOpcode::Sub => match integers {
+            Some((x, y)) => Value::Integer(x.checked_sub(y)?),
+            None => float(a - b),
+        },
+        BinaryOpcode::Mul => match integers {
+            Some((x, y)) => Value::Integer(x.checked_mul(y)?),
+            None => Value::Float(a * b),
+        },
+        BinaryOpcode::Div => match integers {
+            Some((x, y)) if y != 0 && x % y == 0 => Value::Integer(x / y),
+            _ => Value::Float(a / b),
+        },
+        BinaryOpcode::Rem => match integers {
+            Some((_, 0)) => return None,
+            Some((x, y)) => {
+                let remainder = x % y;
+                Value::Integer(if remainder < 0 {
+                    remainder + y
+                } else {
+                    remainder
+                })
+            }
+            None => {
+                let remainder = a % b;
+                Value::Float(if remainder < 0.0 {
+                    remainder + b
+                } else {
+                    remainder
+                })
+            }
+        },
+        BinaryOpcode::Pow => match integers {
+            Some((x, y)) if y >= 0 => Value::Integer(x.checked_pow(u32::try_from(y).ok()?)?),
+            _ => Value::Float(a.powf(b)),
+        },
+        BinaryOpcode::Conj => Value::Boolean(is_truthy(left) && is_truthy(right)),
+        BinaryOpcode::Disj => Value::Boolean(is_truthy(left) || is_truthy(right)),
+        BinaryOpcode::Equals => Value::Boolean(a == b),
+        BinaryOpcode::NotEquals => Value::Boolean(a != b),
+        BinaryOpcode::Greater => Value::Boolean(a > b),
+        BinaryOpcode::GreaterEquals => Value::Boolean(a >= b),
+        BinaryOpcode::Lower => Value::Boolean(a < b),
+        BinaryOpcode::LowerEquals => Value::Boolean(a <= b),
+        BinaryOpcode::BitAnd => Value::Integer(as_integer(left) & as_integer(right)),
+        BinaryOpcode::BitOr => Value::Integer(as_integer(left) | as_integer(right)),
+        BinaryOpcode::BitXor => Value::Integer(as_integer(left) ^ as_integer(right)),
+        BinaryOpcode::ShiftLeft => {
+            Value::Integer(as_integer(left).checked_shl(shift_amount(right)?)?)
+        }
+        BinaryOpcode::ShiftRight => {
+            Value::Integer(as_integer(left).checked_shr(shift_amount(right)?)?)
+        }
+        BinaryOpcode::ShiftRightLogical => {
+            let shifted = (as_integer(left) as u64).checked_shr(shift_amount(right)?)?;
+            Value::Integer(shifted as i64)
+        }
+    };
+
+    Some(value)
+}
+
+/// The dimension of the sum or difference of two values, if it has one.
+/// Adding a plain number to a quantity keeps its dimension, mixing dimensions loses it.
+fn quantity_dimension(left: Value, right: Value) -> Option<Dimension> {
+    match (left, right) {
+        (Value::Quantity(_, a), Value::Quantity(_, b)) => {
+            (a.dimension() == b.dimension()).then_some(a.dimension())
+        }
+        (Value::Quantity(_, unit), _) | (_, Value::Quantity(_, unit)) => Some(unit.dimension()),
+        _ => None,
+    }
+}
+
+/// Whether a value counts as true in a condition.
+fn is_truthy(value: Value) -> bool {
+    as_float(value) != 0.0
+}
+
+fn as_float(value: Value) -> f64 {
+    match value {
+        Value::Integer(i) => i as f64,
+        Value::Float(f) => f,
+        Value::Quantity(value, unit) => unit.normalize(value),
+        Value::Boolean(b) => {
+            if b {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+/// Converts a value to an integer for bitwise operations, truncating floats like IC10 does.
+fn as_integer(value: Value) -> i64 {
+    match value {
+        Value::Integer(i) => i,
+        value => as_float(value) as i64,
+    }
+}
+
+fn shift_amount(value: Value) -> Option<u32> {
+    u32::try_from(as_integer(value)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::Error,
+        testing::{compile, compile_error},
+    };
+    use ayysee_parser::ast::Unit;
+
+    #[test]
+    fn test_ic10_semantics() {
+        let fold = |a, op, b| evaluate_binary_op(a, op, b);
+
+        assert!(matches!(
+            fold(Value::Integer(-7), BinaryOpcode::Rem, Value::Integer(3)),
+            Some(Value::Integer(2))
+        ));
+        assert!(matches!(
+            fold(Value::Integer(7), BinaryOpcode::Div, Value::Integer(2)),
+            Some(Value::Float(f)) if f == 3.5
+        ));
+        assert!(matches!(
+            fold(Value::Integer(8), BinaryOpcode::Div, Value::Integer(2)),
+            Some(Value::Integer(4))
+        ));
+        assert!(fold(
+            Value::Integer(i64::MAX),
+            BinaryOpcode::Add,
+            Value::Integer(1)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_quantities() {
+        let celsius = Value::Quantity(20.0, Unit::Celsius);
+
+        assert_eq!(as_float(celsius), 293.15);
+        assert_eq!(as_float(Value::Quantity(1.0, Unit::MegaPascal)), 1000.0);
+        assert!(matches!(
+            evaluate_binary_op(celsius, BinaryOpcode::Add, Value::Integer(5)),
+            Some(Value::Quantity(k, Unit::Kelvin)) if k == 298.15
+        ));
+        assert!(matches!(
+            evaluate_binary_op(celsius, BinaryOpcode::Mul, Value::Integer(2)),
+            Some(Value::Float(_))
+        ));
+    }
+
+    #[test]
+    fn test_constants_are_not_variables() {
+        let program = compile("const X = 5; fn main() { d1.Setting = X + 1; }");
+        assert_eq!(program.lines[3], "move r1 6");
+
+        // the constant would win over the variable wherever the name is used
+        for source in [
+            "const X = 5; fn main() { let X = d0.Setting; d1.Setting = X + 1; }",
+            "const X = 5; fn f(X) { return X; } fn main() { d1.Setting = f(1); }",
+            "const X = 5; fn main() { for X in 0..3 { d1.Setting = X; } }",
+            "fn main() { let X = d0.Setting; const X = 5; d1.Setting = X; }",
+        ] {
+            assert!(
+                matches!(compile_error(source), Error::ConstantRedefined(name) if name == "X"),
+                "{source}"
+            );
+        }
+    }
+}
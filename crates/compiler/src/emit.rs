@@ -0,0 +1,767 @@
+use crate::{
+    codegen::CodeGenerator, expr::constant_operand, regalloc::allocate, stack::Frame,
+    util::function_return, Location,
+};
+use ayysee_ir::{
+    cfg::Cfg, liveness::Liveness, BinaryOp, BlockId, Comparison, Condition, Function, Op, Operand,
+    Program, Terminator, UnaryOp, VReg,
+};
+use ayysee_parser::ast::Value;
+use stationeers_mips::{
+    instructions::{
+        Arithmetic, DeviceIo, FlowControl, Instruction, Logic, Misc, VariableSelection,
+    },
+    types::{Line, Number, Register, RegisterOrNumber, Slot},
+};
+use std::collections::{BTreeSet, HashSet};
+
+/// The minimum number of cases for a switch to be emitted as a jump table.
+const JUMP_TABLE_MIN_CASES: usize = 4;
+
+/// Emits the MIPS code of a program.
+///
+/// The top-level initialisation comes first, so that execution starts there. It ends by calling
+/// `main`, and the functions follow it.
+pub(crate) fn emit_program(program: &Program, codegen: &mut CodeGenerator) {
+    emit_function(&program.init, false, codegen);
+    for function in &program.functions {
+        emit_function(function, true, codegen);
+    }
+}
+
+/// Emits a function. Functions that can be called start with a label of their name and receive
+/// their arguments according to the calling convention described at `emit_call`.
+fn emit_function(function: &Function, callable: bool, codegen: &mut CodeGenerator) {
+    let cfg = Cfg::new(function);
+    let liveness = Liveness::new(function, &cfg);
+    let mut frame = Frame::new(allocate(function, &liveness));
+    let labelled = labelled_blocks(function);
+
+    if callable {
+        codegen.add_doc_lines(&function.docs);
+        codegen.add_label(function.name.clone());
+        codegen.add_doc_trailing(&function.docs);
+    }
+
+    // function prologue
+    frame.enter(codegen);
+    if callable {
+        receive_parameters(function, &frame, codegen);
+    }
+
+    for id in function.block_ids() {
+        let block = function.block(id);
+        if labelled.contains(&id) {
+            codegen.add_label(block.name.clone());
+        }
+
+        let live_after = liveness.live_after_ops(function, id);
+        for (op, live) in block.ops.iter().zip(&live_after) {
+            emit_op(op, live, callable, &mut frame, codegen);
+        }
+
+        let next = BlockId(id.0 + 1);
+        emit_terminator(function, &block.terminator, next, &mut frame, codegen);
+    }
+}
+
+/// Moves the arguments into the locations of the parameters.
+/// The first four are passed in `r0`-`r3` and the others were pushed onto the stack in order,
+/// right before the function was called.
+fn receive_parameters(function: &Function, frame: &Frame, codegen: &mut CodeGenerator) {
+    for (i, parameter) in function.parameters.iter().enumerate().take(4) {
+        frame.assign(*parameter, Register::from(i as u8).into(), codegen);
+    }
+
+    let count = function.parameters.len() as i32;
+    for (i, parameter) in function.parameters.iter().enumerate().skip(4) {
+        // the arguments on the stack are right below the frame
+        let slot = i as i32 - count;
+        match frame.location(*parameter) {
+            Some(Location::Register(register)) => frame.load_slot(slot, register, codegen),
+            Some(Location::Stack(_)) => {
+                frame.load_slot(slot, Register::R0, codegen);
+                frame.assign(*parameter, Register::R0.into(), codegen);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Emits a single operation.
+/// `live_after` holds the registers that are still needed after it, which calls must preserve.
+/// `callable` tells whether the function returns to a caller, which calls must preserve `ra` for.
+fn emit_op(
+    op: &Op,
+    live_after: &BTreeSet<VReg>,
+    callable: bool,
+    frame: &mut Frame,
+    codegen: &mut CodeGenerator,
+) {
+    match op {
+        Op::Move { dest, source } => {
+            // values on the stack are loaded straight into the register of the destination
+            let value = frame.operand(source, frame.write_register(*dest), codegen);
+            frame.assign(*dest, value, codegen);
+        }
+        Op::Binary { dest, op, a, b } => {
+            let a = frame.operand(a, Register::R1, codegen);
+            let b = frame.operand(b, Register::R2, codegen);
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(binary_instruction(*op, register, a, b));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::Unary { dest, op, a } => {
+            let a = frame.operand(a, Register::R1, codegen);
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(unary_instruction(*op, register, a));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::Select {
+            dest,
+            condition,
+            then,
+            otherwise,
+        } => {
+            let a = frame.operand(condition, Register::R1, codegen);
+            let b = frame.operand(then, Register::R2, codegen);
+            let c = frame.operand(otherwise, Register::R3, codegen);
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(Instruction::from(VariableSelection::Select {
+                register,
+                a,
+                b,
+                c,
+            }));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::Random { dest } => {
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(Instruction::from(Arithmetic::Random { register }));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::Call {
+            dest,
+            function,
+            arguments,
+        } => emit_call(
+            *dest, function, arguments, live_after, callable, frame, codegen,
+        ),
+        Op::Alias { name, device, docs } => {
+            codegen.add_doc_lines(docs);
+            codegen.add_instruction(
+                Misc::Alias {
+                    name: name.clone(),
+                    target: device.to_string(),
+                }
+                .into(),
+            );
+            codegen.add_doc_trailing(docs);
+        }
+        Op::LoadDevice {
+            dest,
+            device,
+            variable,
+        } => {
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(Instruction::from(DeviceIo::LoadDeviceVariable {
+                register,
+                device: *device,
+                variable: variable.clone(),
+            }));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::StoreDevice {
+            device,
+            variable,
+            value,
+        } => {
+            let register = frame.register_operand(value, Register::R1, codegen);
+            codegen.add_instruction(Instruction::from(DeviceIo::StoreDeviceVariable {
+                device: *device,
+                variable: variable.clone(),
+                register,
+            }));
+        }
+        Op::LoadSlot {
+            dest,
+            device,
+            slot,
+            variable,
+        } => {
+            let slot = frame.operand(slot, Register::R1, codegen);
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(Instruction::from(DeviceIo::LoadSlot {
+                register,
+                device: *device,
+                slot: Slot::from(slot),
+                variable: variable.clone(),
+            }));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::StoreSlot {
+            device,
+            slot,
+            variable,
+            value,
+        } => {
+            let register = frame.register_operand(value, Register::R1, codegen);
+            let slot = frame.operand(slot, Register::R2, codegen);
+            codegen.add_instruction(Instruction::from(DeviceIo::StoreSlot {
+                device: *device,
+                slot: Slot::from(slot),
+                variable: variable.clone(),
+                register,
+            }));
+        }
+        Op::LoadReagent {
+            dest,
+            device,
+            mode,
+            reagent,
+        } => {
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(Instruction::from(DeviceIo::LoadReagent {
+                register,
+                device: *device,
+                reagent_mode: mode.clone(),
+                reagent: reagent.clone(),
+            }));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::LoadBatch {
+            dest,
+            type_hash,
+            variable,
+            mode,
+        } => {
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(Instruction::from(DeviceIo::LoadBatch {
+                register,
+                type_hash: type_hash.clone(),
+                variable: variable.clone(),
+                batch_mode: mode.clone(),
+            }));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::StoreBatch {
+            type_hash,
+            variable,
+            value,
+        } => {
+            let register = frame.register_operand(value, Register::R1, codegen);
+            codegen.add_instruction(Instruction::from(DeviceIo::StoreBatch {
+                type_hash: type_hash.clone(),
+                variable: variable.clone(),
+                register,
+            }));
+        }
+        Op::Connected { dest, device } => {
+            let register = frame.write_register(*dest);
+            codegen.add_instruction(Instruction::from(VariableSelection::SelectDeviceSet {
+                register,
+                device: *device,
+            }));
+            frame.finish_write(*dest, codegen);
+        }
+        Op::Require { device } => {
+            // skip the halt if the device is connected
+            codegen.add_instruction(Instruction::from(DeviceIo::BranchRelativeDeviceSet {
+                device: *device,
+                line: Number::Int(2).into(),
+            }));
+            codegen.add_instruction(Instruction::from(Misc::Halt));
+        }
+        Op::Yield => codegen.add_instruction(Instruction::from(Misc::Yield)),
+        Op::Comment(comment) => codegen.add_comment_line(comment.clone()),
+    }
+}
+
+/// Calls a function. If the function returns a value it is kept in `dest`.
+///
+/// The calling convention is:
+/// - The caller saves `ra` and every register that holds a value it still needs on the stack.
+///   The top-level initialisation is never returned from, so it does not save `ra`.
+/// - Arguments after the fourth are pushed onto the stack in order.
+/// - The first four arguments are passed in `r0`-`r3`.
+/// - The callee returns its value in `r0` and removes its own frame from the stack before
+///   jumping back to `ra`.
+/// - The caller removes the stack arguments and restores the saved registers.
+fn emit_call(
+    dest: Option<VReg>,
+    function: &str,
+    arguments: &[Operand],
+    live_after: &BTreeSet<VReg>,
+    save_return_address: bool,
+    frame: &mut Frame,
+    codegen: &mut CodeGenerator,
+) {
+    // save registers.
+    // The callee uses the same registers for its own values.
+    let mut saved = Vec::new();
+    for register in live_after
+        .iter()
+        .filter(|register| Some(**register) != dest)
+    {
+        if let Some(Location::Register(register)) = frame.location(*register) {
+            if !saved.contains(&register) {
+                saved.push(register);
+            }
+        }
+    }
+    saved.sort_by_key(|register| *register as u8);
+    if save_return_address {
+        saved.insert(0, Register::Ra);
+    }
+    for register in &saved {
+        frame.push((*register).into(), codegen);
+    }
+
+    // pass arguments on the stack
+    for argument in arguments.iter().skip(4) {
+        let value = frame.operand(argument, Register::R0, codegen);
+        frame.push(value, codegen);
+    }
+
+    // pass arguments in registers.
+    // Values are never kept in r0-r3, so no argument is overwritten by another.
+    for (i, argument) in arguments.iter().enumerate().take(4) {
+        let register = Register::from(i as u8);
+        let value = frame.operand(argument, register, codegen);
+        if !matches!(value, RegisterOrNumber::Register(source) if source == register) {
+            codegen.add_instruction(Instruction::from(Misc::Move { register, a: value }));
+        }
+    }
+
+    codegen.add_instruction(
+        FlowControl::JumpAndLink {
+            a: Line::Label(function.to_string()),
+        }
+        .into(),
+    );
+
+    // deallocate arguments and restore the saved registers
+    frame.discard(arguments.len().saturating_sub(4) as i32, codegen);
+    for register in saved.iter().rev() {
+        frame.pop(*register, codegen);
+    }
+
+    // the return value is passed in r0
+    if let Some(dest) = dest {
+        frame.assign(dest, Register::R0.into(), codegen);
+    }
+}
+
+/// Emits the end of a block. `next` is the block that is laid out after it, which is reached by
+/// falling through instead of jumping.
+fn emit_terminator(
+    function: &Function,
+    terminator: &Terminator,
+    next: BlockId,
+    frame: &mut Frame,
+    codegen: &mut CodeGenerator,
+) {
+    let line = |block: BlockId| Line::Label(function.block(block).name.clone());
+
+    match terminator {
+        Terminator::Jump(target) => {
+            if *target != next {
+                emit_jump(line(*target), codegen);
+            }
+        }
+        Terminator::Branch {
+            condition,
+            then,
+            otherwise,
+        } => {
+            if *otherwise == next {
+                emit_branch(condition.clone(), line(*then), frame, codegen);
+            } else if *then == next {
+                emit_branch(condition.clone().invert(), line(*otherwise), frame, codegen);
+            } else {
+                emit_branch(condition.clone(), line(*then), frame, codegen);
+                emit_jump(line(*otherwise), codegen);
+            }
+        }
+        Terminator::Switch {
+            value,
+            cases,
+            default,
+        } => {
+            let value = frame.operand(value, Register::R1, codegen);
+            if uses_jump_table(cases) {
+                emit_jump_table(value, cases, line(*default), &line, codegen);
+            } else {
+                for (case, block) in cases {
+                    codegen.add_instruction(Instruction::from(FlowControl::BranchEqual {
+                        a: value.clone(),
+                        b: constant_operand(Value::Integer(*case)).into(),
+                        c: line(*block),
+                    }));
+                }
+                if *default != next {
+                    emit_jump(line(*default), codegen);
+                }
+            }
+        }
+        Terminator::Return(value) => {
+            // the return value is passed back in r0
+            if let Some(value) = value {
+                let value = frame.operand(value, Register::R0, codegen);
+                if !matches!(value, RegisterOrNumber::Register(Register::R0)) {
+                    codegen.add_instruction(Instruction::from(Misc::Move {
+                        register: Register::R0,
+                        a: value,
+                    }));
+                }
+            }
+
+            // function epilogue
+            frame.leave(codegen);
+            function_return!(codegen);
+        }
+        Terminator::Halt => codegen.add_instruction(Instruction::from(Misc::Halt)),
+        Terminator::Restart => {
+            frame.leave(codegen);
+            codegen.add_instruction(FlowControl::Jump { a: 0.into() }.into());
+        }
+    }
+}
+
+/// Emits an unconditional jump.
+fn emit_jump(line: Line, codegen: &mut CodeGenerator) {
+    codegen.add_instruction(Instruction::from(FlowControl::Jump { a: line }));
+}
+
+/// Emits a branch to a line that is taken if the condition holds.
+/// Comparisons with 0 use the shorter forms of the branch instructions.
+fn emit_branch(condition: Condition, line: Line, frame: &Frame, codegen: &mut CodeGenerator) {
+    let instruction = match condition {
+        Condition::Compare { comparison, a, b } => {
+            let a = frame.operand(&a, Register::R1, codegen);
+            if let Operand::Constant(Number::Int(0)) = b {
+                let b = line;
+                match comparison {
+                    Comparison::Equal => FlowControl::BranchEqualZero { a, b },
+                    Comparison::NotEqual => FlowControl::BranchNotEqualZero { a, b },
+                    Comparison::Greater => FlowControl::BranchGreaterThanZero { a, b },
+                    Comparison::GreaterOrEqual => FlowControl::BranchGreaterOrEqualZero { a, b },
+                    Comparison::Less => FlowControl::BranchLessThanZero { a, b },
+                    Comparison::LessOrEqual => FlowControl::BranchLessOrEqualZero { a, b },
+                }
+                .into()
+            } else {
+                let b = frame.operand(&b, Register::R2, codegen);
+                let c = line;
+                match comparison {
+                    Comparison::Equal => FlowControl::BranchEqual { a, b, c },
+                    Comparison::NotEqual => FlowControl::BranchNotEqual { a, b, c },
+                    Comparison::Greater => FlowControl::BranchGreaterThan { a, b, c },
+                    Comparison::GreaterOrEqual => FlowControl::BranchGreaterOrEqual { a, b, c },
+                    Comparison::Less => FlowControl::BranchLessThan { a, b, c },
+                    Comparison::LessOrEqual => FlowControl::BranchLessOrEqual { a, b, c },
+                }
+                .into()
+            }
+        }
+        Condition::DeviceSet(device) => DeviceIo::BranchDeviceSet { device, line }.into(),
+        Condition::DeviceNotSet(device) => DeviceIo::BranchDeviceNotSet { device, line }.into(),
+    };
+    codegen.add_instruction(instruction);
+}
+
+/// Whether a switch is dense enough to be dispatched with a jump table.
+fn uses_jump_table(cases: &[(i64, BlockId)]) -> bool {
+    let min = cases.iter().map(|(case, _)| *case).min().unwrap_or(0);
+    let max = cases.iter().map(|(case, _)| *case).max().unwrap_or(0);
+    let span = max.abs_diff(min) as usize + 1;
+
+    cases.len() >= JUMP_TABLE_MIN_CASES && span <= cases.len() * 2
+}
+
+/// Emits a `jr` jump table that holds one `j` for every value between the smallest and largest
+/// case. Like the comparisons of a smaller switch, it sends values that are not integers to the
+/// default.
+fn emit_jump_table(
+    value: RegisterOrNumber,
+    cases: &[(i64, BlockId)],
+    default: Line,
+    line: &dyn Fn(BlockId) -> Line,
+    codegen: &mut CodeGenerator,
+) {
+    let min = cases.iter().map(|(case, _)| *case).min().unwrap_or(0);
+    let max = cases.iter().map(|(case, _)| *case).max().unwrap_or(0);
+
+    // values that are not integers or are outside of the table go to the default
+    codegen.add_instruction(Instruction::from(Arithmetic::Truncate {
+        register: Register::R0,
+        a: value.clone(),
+    }));
+    codegen.add_instruction(Instruction::from(FlowControl::BranchNotEqual {
+        a: Register::R0.into(),
+        b: value,
+        c: default.clone(),
+    }));
+    codegen.add_instruction(Instruction::from(FlowControl::BranchLessThan {
+        a: Register::R0.into(),
+        b: constant_operand(Value::Integer(min)).into(),
+        c: default.clone(),
+    }));
+    codegen.add_instruction(Instruction::from(FlowControl::BranchGreaterThan {
+        a: Register::R0.into(),
+        b: constant_operand(Value::Integer(max)).into(),
+        c: default.clone(),
+    }));
+
+    // `jr 1` jumps to the next line, which is the entry for the smallest value
+    if min != 1 {
+        codegen.add_instruction(Instruction::from(Arithmetic::Subtract {
+            register: Register::R0,
+            a: Register::R0.into(),
+            b: constant_operand(Value::Integer(min - 1)).into(),
+        }));
+    }
+    codegen.add_instruction(Instruction::from(FlowControl::JumpRelative {
+        a: Register::R0.into(),
+    }));
+
+    for value in min..=max {
+        let target = match cases.iter().find(|(case, _)| *case == value) {
+            Some((_, block)) => line(*block),
+            None => default.clone(),
+        };
+        emit_jump(target, codegen);
+        codegen.add_comment(format!("case {value}"));
+    }
+}
+
+/// Finds the blocks that are jumped to, which need a label.
+/// Blocks that are only reached by falling through from the block before them do not.
+fn labelled_blocks(function: &Function) -> HashSet<BlockId> {
+    let mut labelled = HashSet::new();
+    for id in function.block_ids() {
+        let next = BlockId(id.0 + 1);
+        match &function.block(id).terminator {
+            Terminator::Jump(target) if *target != next => {
+                labelled.insert(*target);
+            }
+            Terminator::Branch {
+                then, otherwise, ..
+            } => {
+                // mirrors `emit_terminator`
+                if *otherwise == next {
+                    labelled.insert(*then);
+                } else if *then == next {
+                    labelled.insert(*otherwise);
+                } else {
+                    labelled.insert(*then);
+                    labelled.insert(*otherwise);
+                }
+            }
+            Terminator::Switch { cases, default, .. } => {
+                labelled.extend(cases.iter().map(|(_, block)| *block));
+                if *default != next || uses_jump_table(cases) {
+                    labelled.insert(*default);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    labelled
+}
+
+/// Creates the instruction that applies a binary operation to two operands.
+fn binary_instruction(
+    op: BinaryOp,
+    register: Register,
+    a: RegisterOrNumber,
+    b: RegisterOrNumber,
+) -> Instruction {
+    match op {
+        BinaryOp::Add => Instruction::from(Arithmetic::Add { register, a, b }),
+        BinaryOp::Sub => Instruction::from(Arithmetic::Subtract { register, a, b }),
+        BinaryOp::Mul => Instruction::from(Arithmetic::Multiply { register, a, b }),
+        BinaryOp::Div => Instruction::from(Arithmetic::Divide { register, a, b }),
+        BinaryOp::Mod => Instruction::from(Arithmetic::Mod { register, a, b }),
+        BinaryOp::Min => Instruction::from(Arithmetic::Minimum { register, a, b }),
+        BinaryOp::Max => Instruction::from(Arithmetic::Maximum { register, a, b }),
+        BinaryOp::And => Instruction::from(Logic::And { register, a, b }),
+        BinaryOp::Or => Instruction::from(Logic::Or { register, a, b }),
+        BinaryOp::Xor => Instruction::from(Logic::Xor { register, a, b }),
+        BinaryOp::Nor => Instruction::from(Logic::Nor { register, a, b }),
+        BinaryOp::ShiftLeft => Instruction::from(Logic::ShiftLeftLogical { register, a, b }),
+        BinaryOp::ShiftRightArithmetic => {
+            Instruction::from(Logic::ShiftRightArithmetic { register, a, b })
+        }
+        BinaryOp::ShiftRightLogical => {
+            Instruction::from(Logic::ShiftRightLogical { register, a, b })
+        }
+        BinaryOp::Compare(comparison) => Instruction::from(match comparison {
+            Comparison::Equal => VariableSelection::SelectEqual { register, a, b },
+            Comparison::NotEqual => VariableSelection::SelectNotEqual { register, a, b },
+            Comparison::Greater => VariableSelection::SelectGreaterThan { register, a, b },
+            Comparison::GreaterOrEqual => {
+                VariableSelection::SelectGreaterOrEqual { register, a, b }
+            }
+            Comparison::Less => VariableSelection::SelectLessThan { register, a, b },
+            Comparison::LessOrEqual => VariableSelection::SelectLessOrEqual { register, a, b },
+        }),
+    }
+}
+
+/// Creates the instruction that applies a unary operation to an operand.
+fn unary_instruction(op: UnaryOp, register: Register, a: RegisterOrNumber) -> Instruction {
+    match op {
+        UnaryOp::Abs => Arithmetic::AbsoluteValue { register, a }.into(),
+        UnaryOp::Sqrt => Arithmetic::SquareRoot { register, a }.into(),
+        UnaryOp::Sin => Arithmetic::Sine { register, a }.into(),
+        UnaryOp::Cos => Arithmetic::Cosine { register, a }.into(),
+        UnaryOp::Tan => Arithmetic::Tangent { register, a }.into(),
+        UnaryOp::Asin => Arithmetic::ArcSine { register, a }.into(),
+        UnaryOp::Acos => Arithmetic::ArcCosine { register, a }.into(),
+        UnaryOp::Atan => Arithmetic::ArcTangent { register, a }.into(),
+        UnaryOp::Exp => Arithmetic::Exponent { register, a }.into(),
+        UnaryOp::Log => Arithmetic::Logarithm { register, a }.into(),
+        UnaryOp::Floor => Arithmetic::Floor { register, a }.into(),
+        UnaryOp::Ceil => Arithmetic::Ceiling { register, a }.into(),
+        UnaryOp::Round => Arithmetic::Round { register, a }.into(),
+        UnaryOp::Trunc => Arithmetic::Truncate { register, a }.into(),
+        UnaryOp::IsZero => VariableSelection::SelectEqualZero { register, a }.into(),
+        UnaryOp::NotZero => VariableSelection::SelectNotEqualZero { register, a }.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{compile, Compiled};
+
+    /// Compiles a match over `d0.Mode` whose default writes 0 to `d1.Setting`.
+    fn compile_match(cases: &str) -> Compiled {
+        compile(&format!(
+            "fn main() {{
+                match d0.Mode {{ {cases} _ => {{ d1.Setting = 0; }}, }}
+            }}"
+        ))
+    }
+
+    #[test]
+    fn test_jump_tables() {
+        // dense cases from 0 go through a table with an entry for every value in between
+        let program = compile_match(
+            "0 => { d1.Setting = 1; }, 1 => { d1.Setting = 2; },
+            2 => { d1.Setting = 3; }, 4 => { d1.Setting = 4; },",
+        );
+        assert_eq!(
+            program.lines[4..16],
+            [
+                "trunc r0 r4",
+                "bne r0 r4 main.match_0_4",
+                "blt r0 0 main.match_0_4",
+                "bgt r0 4 main.match_0_4",
+                "sub r0 r0 -1",
+                "jr r0",
+                "j main.match_0_0",
+                "j main.match_0_1",
+                "j main.match_0_2",
+                "j main.match_0_4",
+                "j main.match_0_3",
+                "main.match_0_0:",
+            ]
+        );
+        let run = |mode| program.writes(&[("d0 Mode", mode)]);
+        assert_eq!(run(0.0), ["d1 Setting 1"]);
+        assert_eq!(run(2.0), ["d1 Setting 3"]);
+        assert_eq!(run(3.0), ["d1 Setting 0"]);
+        assert_eq!(run(4.0), ["d1 Setting 4"]);
+        assert_eq!(run(-1.0), ["d1 Setting 0"]);
+        assert_eq!(run(5.0), ["d1 Setting 0"]);
+        // values that are not integers match no case, as they would when compared one by one
+        assert_eq!(run(1.5), ["d1 Setting 0"]);
+        assert_eq!(run(-0.5), ["d1 Setting 0"]);
+
+        // a table that starts at 1 is indexed by the value itself
+        let program = compile_match(
+            "1 => { d1.Setting = 1; }, 2 => { d1.Setting = 2; },
+            3 => { d1.Setting = 3; }, 4 => { d1.Setting = 4; },",
+        );
+        assert_eq!(program.after("bgt r0 4 main.match_0_4"), "jr r0");
+        let run = |mode| program.writes(&[("d0 Mode", mode)]);
+        assert_eq!(run(1.0), ["d1 Setting 1"]);
+        assert_eq!(run(4.0), ["d1 Setting 4"]);
+
+        // negative cases move the smallest one to the first entry
+        let program = compile_match(
+            "-2 => { d1.Setting = 1; }, -1 => { d1.Setting = 2; },
+            1 => { d1.Setting = 3; }, 2 => { d1.Setting = 4; },",
+        );
+        assert_eq!(
+            program.lines[6..13],
+            [
+                "blt r0 -2 main.match_0_4",
+                "bgt r0 2 main.match_0_4",
+                "sub r0 r0 -3",
+                "jr r0",
+                "j main.match_0_0",
+                "j main.match_0_1",
+                "j main.match_0_4",
+            ]
+        );
+        let run = |mode| program.writes(&[("d0 Mode", mode)]);
+        assert_eq!(run(-2.0), ["d1 Setting 1"]);
+        assert_eq!(run(-1.0), ["d1 Setting 2"]);
+        assert_eq!(run(0.0), ["d1 Setting 0"]);
+        assert_eq!(run(2.0), ["d1 Setting 4"]);
+        assert_eq!(run(-3.0), ["d1 Setting 0"]);
+
+        // the offset of a table that starts at the smallest 32-bit integer does not fit in 32 bits
+        let program = compile_match(
+            "-2147483648 => { d1.Setting = 1; }, -2147483647 => { d1.Setting = 2; },
+            -2147483646 => { d1.Setting = 3; }, -2147483645 => { d1.Setting = 4; },",
+        );
+        assert!(program.contains("sub r0 r0 -2147483649"));
+        let run = |mode| program.writes(&[("d0 Mode", mode)]);
+        assert_eq!(run(-2147483648.0), ["d1 Setting 1"]);
+        assert_eq!(run(-2147483645.0), ["d1 Setting 4"]);
+        assert_eq!(run(-2147483649.0), ["d1 Setting 0"]);
+    }
+
+    #[test]
+    fn test_compared_cases() {
+        // sparse cases are compared one by one
+        let program = compile_match(
+            "1 => { d1.Setting = 1; }, 10 => { d1.Setting = 2; },
+            100 => { d1.Setting = 3; }, 1000 => { d1.Setting = 4; },",
+        );
+        assert_eq!(
+            program.lines[4..9],
+            [
+                "beq r4 1 main.match_0_0",
+                "beq r4 10 main.match_0_1",
+                "beq r4 100 main.match_0_2",
+                "beq r4 1000 main.match_0_3",
+                "j main.match_0_4",
+            ]
+        );
+        let run = |mode| program.writes(&[("d0 Mode", mode)]);
+        assert_eq!(run(100.0), ["d1 Setting 3"]);
+        assert_eq!(run(5.0), ["d1 Setting 0"]);
+        assert_eq!(run(1.5), ["d1 Setting 0"]);
+
+        // patterns outside of the range of 32-bit integers are compared exactly
+        let program =
+            compile_match("4294967296 => { d1.Setting = 1; }, -2147483649 => { d1.Setting = 2; },");
+        assert_eq!(
+            program.lines[4..6],
+            [
+                "beq r4 4294967296 main.match_0_0",
+                "beq r4 -2147483649 main.match_0_1",
+            ]
+        );
+        let run = |mode| program.writes(&[("d0 Mode", mode)]);
+        assert_eq!(run(4294967296.0), ["d1 Setting 1"]);
+        assert_eq!(run(-2147483649.0), ["d1 Setting 2"]);
+        assert_eq!(run(0.0), ["d1 Setting 0"]);
+        assert_eq!(run(2147483647.0), ["d1 Setting 0"]);
+    }
+}
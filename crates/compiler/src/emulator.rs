@@ -0,0 +1,350 @@
+//! A small IC10 interpreter that the tests use to check what compiled programs do.
+//!
+//! It runs the text of a program, so it checks exactly what would be pasted into the game. Only
+//! the instructions that the compiler emits are supported, and anything else panics.
+
+use std::collections::{HashMap, HashSet};
+
+/// The number of instructions after which a program is stopped.
+const MAX_STEPS: usize = 100_000;
+
+/// How a program stopped running.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Exit {
+    /// It ran `hcf`
+    Halted,
+    /// It ran past its last line
+    Ended,
+    /// It was still running after `MAX_STEPS` instructions
+    Timeout,
+}
+
+/// What running a program did.
+#[derive(Debug)]
+pub(crate) struct Run {
+    /// Every write to a device, e.g. `d0 Setting 5`
+    pub(crate) writes: Vec<String>,
+    pub(crate) exit: Exit,
+    /// The number of instructions that were executed
+    pub(crate) steps: usize,
+}
+
+/// The devices a program runs against.
+pub(crate) struct Emulator {
+    /// Device variables by `device variable`, slot variables by `device slot variable`, reagents
+    /// by `device mode reagent` and batch reads by `hash variable mode`
+    values: HashMap<String, f64>,
+    disconnected: HashSet<String>,
+}
+
+impl Emulator {
+    pub(crate) fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+            disconnected: HashSet::new(),
+        }
+    }
+
+    /// Sets a value that the program can read, e.g. `"d0 Temperature"`.
+    pub(crate) fn set(mut self, key: &str, value: f64) -> Self {
+        self.values.insert(key.to_string(), value);
+        self
+    }
+
+    /// Removes the device from a pin. All pins are connected otherwise.
+    pub(crate) fn disconnect(mut self, device: &str) -> Self {
+        self.disconnected.insert(device.to_string());
+        self
+    }
+
+    pub(crate) fn run(&self, code: &str) -> Run {
+        let lines: Vec<Vec<&str>> = code
+            .lines()
+            .map(|line| {
+                let line = line.split('#').next().unwrap_or_default();
+                line.split_whitespace().collect()
+            })
+            .collect();
+        let labels: HashMap<&str, usize> = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match line.as_slice() {
+                [label] => label.strip_suffix(':').map(|label| (label, i)),
+                _ => None,
+            })
+            .collect();
+
+        let mut state = State {
+            registers: HashMap::new(),
+            stack: vec![0.0; 512],
+            aliases: HashMap::new(),
+            labels,
+        };
+        let mut values = self.values.clone();
+        let mut writes = Vec::new();
+
+        let mut pc = 0;
+        let mut steps = 0;
+        while pc < lines.len() {
+            if steps == MAX_STEPS {
+                return Run {
+                    writes,
+                    exit: Exit::Timeout,
+                    steps,
+                };
+            }
+
+            let (op, a) = match lines[pc].split_first() {
+                Some((op, a)) if !op.ends_with(':') => (*op, a),
+                // empty lines and labels
+                _ => {
+                    pc += 1;
+                    continue;
+                }
+            };
+            steps += 1;
+            let mut next = pc + 1;
+
+            match op {
+                "alias" => {
+                    state.aliases.insert(a[0].to_string(), a[1].to_string());
+                }
+                "move" => state.set(a[0], state.value(a[1])),
+                "add" | "sub" | "mul" | "div" | "mod" | "max" | "min" => {
+                    let (x, y) = (state.value(a[1]), state.value(a[2]));
+                    let result = match op {
+                        "add" => x + y,
+                        "sub" => x - y,
+                        "mul" => x * y,
+                        "div" => x / y,
+                        "mod" => {
+                            let result = x % y;
+                            if result < 0.0 {
+                                result + y
+                            } else {
+                                result
+                            }
+                        }
+                        "max" => x.max(y),
+                        _ => x.min(y),
+                    };
+                    state.set(a[0], result);
+                }
+                "and" | "or" | "xor" | "nor" | "sll" | "sla" | "srl" | "sra" => {
+                    let (x, y) = (state.value(a[1]) as i64, state.value(a[2]) as i64);
+                    let result = match op {
+                        "and" => x & y,
+                        "or" => x | y,
+                        "xor" => x ^ y,
+                        "nor" => !(x | y),
+                        "sll" | "sla" => x << y,
+                        "srl" => ((x as u64) >> y) as i64,
+                        _ => x >> y,
+                    };
+                    state.set(a[0], result as f64);
+                }
+                "slt" | "sgt" | "sle" | "sge" | "seq" | "sne" => {
+                    let holds = compare(op, state.value(a[1]), state.value(a[2]));
+                    state.set(a[0], holds as i32 as f64);
+                }
+                "sltz" | "sgtz" | "slez" | "sgez" | "seqz" | "snez" => {
+                    let holds = compare(op, state.value(a[1]), 0.0);
+                    state.set(a[0], holds as i32 as f64);
+                }
+                "select" => {
+                    let value = if state.value(a[1]) != 0.0 {
+                        state.value(a[2])
+                    } else {
+                        state.value(a[3])
+                    };
+                    state.set(a[0], value);
+                }
+                "abs" | "sqrt" | "floor" | "ceil" | "round" | "trunc" | "exp" | "log" | "sin"
+                | "cos" | "tan" | "asin" | "acos" | "atan" => {
+                    let x = state.value(a[1]);
+                    let result = match op {
+                        "abs" => x.abs(),
+                        "sqrt" => x.sqrt(),
+                        "floor" => x.floor(),
+                        "ceil" => x.ceil(),
+                        "round" => x.round(),
+                        "trunc" => x.trunc(),
+                        "exp" => x.exp(),
+                        "log" => x.ln(),
+                        "sin" => x.sin(),
+                        "cos" => x.cos(),
+                        "tan" => x.tan(),
+                        "asin" => x.asin(),
+                        "acos" => x.acos(),
+                        _ => x.atan(),
+                    };
+                    state.set(a[0], result);
+                }
+                "rand" => state.set(a[0], 0.5),
+                "push" => {
+                    let sp = state.get("sp") as usize;
+                    state.stack[sp] = state.value(a[0]);
+                    state.set("sp", (sp + 1) as f64);
+                }
+                "pop" => {
+                    let sp = state.get("sp") as usize - 1;
+                    state.set("sp", sp as f64);
+                    state.set(a[0], state.stack[sp]);
+                }
+                "peek" => {
+                    let sp = state.get("sp") as usize;
+                    state.set(a[0], state.stack[sp - 1]);
+                }
+                "j" => next = state.value(a[0]) as usize,
+                "jal" => {
+                    state.set("ra", next as f64);
+                    next = state.value(a[0]) as usize;
+                }
+                "jr" => next = (pc as f64 + state.value(a[0])) as usize,
+                "blt" | "bgt" | "ble" | "bge" | "beq" | "bne" => {
+                    if compare(op, state.value(a[0]), state.value(a[1])) {
+                        next = state.value(a[2]) as usize;
+                    }
+                }
+                "bltz" | "bgtz" | "blez" | "bgez" | "beqz" | "bnez" => {
+                    if compare(op, state.value(a[0]), 0.0) {
+                        next = state.value(a[1]) as usize;
+                    }
+                }
+                "bdse" | "bdns" | "brdse" | "brdns" => {
+                    let connected = !self.disconnected.contains(&state.device(a[0]));
+                    if connected == op.ends_with("se") {
+                        next = if op.starts_with("br") {
+                            (pc as f64 + state.value(a[1])) as usize
+                        } else {
+                            state.value(a[1]) as usize
+                        };
+                    }
+                }
+                "sdse" | "sdns" => {
+                    let connected = !self.disconnected.contains(&state.device(a[1]));
+                    state.set(a[0], (connected == (op == "sdse")) as i32 as f64);
+                }
+                "l" => {
+                    let key = format!("{} {}", state.device(a[1]), a[2]);
+                    state.set(a[0], values.get(&key).copied().unwrap_or_default());
+                }
+                "s" => {
+                    let key = format!("{} {}", state.device(a[0]), a[1]);
+                    let value = state.value(a[2]);
+                    writes.push(format!("{key} {}", format_number(value)));
+                    values.insert(key, value);
+                }
+                "ls" => {
+                    let slot = format_number(state.value(a[2]));
+                    let key = format!("{} {slot} {}", state.device(a[1]), a[3]);
+                    state.set(a[0], values.get(&key).copied().unwrap_or_default());
+                }
+                "ss" => {
+                    let slot = format_number(state.value(a[1]));
+                    let key = format!("{} {slot} {}", state.device(a[0]), a[2]);
+                    let value = state.value(a[3]);
+                    writes.push(format!("{key} {}", format_number(value)));
+                    values.insert(key, value);
+                }
+                "lr" => {
+                    let key = format!("{} {} {}", state.device(a[1]), a[2], a[3]);
+                    state.set(a[0], values.get(&key).copied().unwrap_or_default());
+                }
+                "lb" => {
+                    let key = format!("{} {} {}", a[1], a[2], a[3]);
+                    state.set(a[0], values.get(&key).copied().unwrap_or_default());
+                }
+                "sb" => {
+                    let value = format_number(state.value(a[2]));
+                    writes.push(format!("{} {} {value}", a[0], a[1]));
+                }
+                "yield" => {}
+                "hcf" => {
+                    return Run {
+                        writes,
+                        exit: Exit::Halted,
+                        steps,
+                    }
+                }
+                _ => panic!("unsupported instruction: {}", lines[pc].join(" ")),
+            }
+
+            pc = next;
+        }
+
+        Run {
+            writes,
+            exit: Exit::Ended,
+            steps,
+        }
+    }
+}
+
+/// The registers and stack of a running program.
+struct State<'a> {
+    registers: HashMap<String, f64>,
+    stack: Vec<f64>,
+    aliases: HashMap<String, String>,
+    labels: HashMap<&'a str, usize>,
+}
+
+impl State<'_> {
+    fn get(&self, register: &str) -> f64 {
+        self.registers.get(register).copied().unwrap_or_default()
+    }
+
+    fn set(&mut self, register: &str, value: f64) {
+        assert!(is_register(register), "{register} is not a register");
+        self.registers.insert(register.to_string(), value);
+    }
+
+    /// Reads a register, label or number.
+    fn value(&self, operand: &str) -> f64 {
+        if is_register(operand) {
+            return self.get(operand);
+        }
+        if let Some(line) = self.labels.get(operand) {
+            return *line as f64;
+        }
+        operand
+            .parse()
+            .unwrap_or_else(|_| panic!("{operand} is not a value"))
+    }
+
+    /// Resolves an alias to the pin it names.
+    fn device(&self, device: &str) -> String {
+        self.aliases
+            .get(device)
+            .cloned()
+            .unwrap_or_else(|| device.to_string())
+    }
+}
+
+fn is_register(operand: &str) -> bool {
+    matches!(operand, "sp" | "ra")
+        || operand
+            .strip_prefix('r')
+            .and_then(|number| number.parse::<u8>().ok())
+            .is_some_and(|number| number < 16)
+}
+
+fn compare(op: &str, a: f64, b: f64) -> bool {
+    match &op[1..3] {
+        "lt" => a < b,
+        "gt" => a > b,
+        "le" => a <= b,
+        "ge" => a >= b,
+        "eq" => a == b,
+        _ => a != b,
+    }
+}
+
+/// Formats whole numbers without a fractional part.
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{value}")
+    }
+}
@@ -2,10 +2,58 @@
 pub enum Error {
     #[error("usage of undefined variable: {0}")]
     UndefinedVariable(String),
+    #[error("usage of undefined device: {0}")]
+    UndefinedDevice(String),
+    #[error("{0} cannot be used as a device type")]
+    InvalidDeviceType(String),
+    #[error("slot variable {0} cannot be written")]
+    ReadOnlySlotVariable(String),
+    #[error("expression cannot be evaluated at compile time: {0}")]
+    NotConstant(String),
+    #[error("jump to undefined label: {0}")]
+    UndefinedLabel(String),
+    #[error("label defined more than once: {0}")]
+    DuplicateLabel(String),
     #[error("usage of undefined function: {0}")]
     UndefinedFunction(String),
+    #[error("function {function} takes {expected} arguments but {found} were given")]
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("function {function} cannot be called with {argument}")]
+    InvalidArgumentType { function: String, argument: String },
+    #[error("{0} is the name of a built-in function")]
+    ReservedName(String),
+    #[error("function {0} does not return a value")]
+    NoReturnValue(String),
+    #[error("function {0} returns a value, but can also return without one")]
+    MissingReturnValue(String),
+    #[error("{0} is a constant and cannot also be a variable")]
+    ConstantRedefined(String),
+    #[error("function {0} is defined more than once")]
+    DuplicateFunction(String),
+    #[error("functions must be defined at the top level: {0}")]
+    NestedFunction(String),
+    #[error("`return` used outside of a function")]
+    ReturnOutsideFunction,
+    #[error("match pattern must be an integer constant: {0}")]
+    InvalidMatchPattern(String),
+    #[error("match pattern {0} is used more than once")]
+    DuplicateMatchPattern(i64),
+    #[error("match pattern {0} is too large to be told apart from its neighbours")]
+    MatchPatternOutOfRange(i64),
+    #[error("match arms after `_` can never be reached")]
+    UnreachableMatchArm,
     #[error("main function not defined")]
     UndefinedMain,
+    #[error("the step of a `for` loop must be a non-zero integer: {0}")]
+    InvalidStep(String),
+    #[error("usage of undefined loop label: {0}")]
+    UndefinedLoopLabel(String),
+    #[error("`{0}` used outside of a loop")]
+    OutsideLoop(String),
     #[error(transparent)]
     Mips(#[from] stationeers_mips::error::Error),
 }
@@ -1,284 +1,859 @@
 use crate::{
+    builder::Builder,
     codegen::CodeGenerator,
+    constant::evaluate_constant,
     error::{Error, Result},
-    stack::Stack,
-    util::{stack_pop, stack_push},
-    Location, Pass,
+    intrinsics::{lower_intrinsic, Intrinsic},
+    statement::lower_function_call,
+    units::lint_comparison,
 };
-
-use ayysee_parser::ast::{Expr, Value};
+use ayysee_ir::{BinaryOp, Comparison, Condition, Op, Operand, Terminator, UnaryOp, VReg};
+use ayysee_parser::ast::{BinaryOpcode, Expr, UnaryOpcode, Value};
 use stationeers_mips::{
-    instructions::{Arithmetic, FlowControl, Instruction, Stack as StackInstruction},
-    types::{Number, Register},
+    hash::hash,
+    types::{BatchMode, DeviceVariable, Number, Reagent, ReagentMode, SlotVariable, TypeHash},
 };
+use std::str::FromStr;
+
+/// The largest constant exponent that is computed by repeated multiplication instead of using
+/// `exp` and `log`.
+const MAX_MULTIPLIED_POWER: i64 = 8;
+
+/// Lowers `base ** exponent` for an exponent that is only known at runtime.
+///
+/// The power is computed as `exp(exponent * log(|base|))`, which needs fixing up for the bases
+/// that the logarithm is not defined for:
+/// - A negative base keeps its sign for odd exponents. A fractional exponent of a negative base
+///   has no real result, so the logarithm of the base itself is taken to give NaN.
+/// - `log(0)` is -infinity, which gives 0 for positive exponents and infinity for negative ones.
+///   `0 ** 0` would be NaN, so a zero exponent always gives 1.
+fn lower_power(base: Operand, exponent: Operand, dest: VReg, builder: &mut Builder) {
+    let binary = |op, a: Operand, b: Operand, builder: &mut Builder| {
+        let dest = builder.new_register();
+        builder.push(Op::Binary { dest, op, a, b });
+        Operand::from(dest)
+    };
+    let unary = |op, a: Operand, builder: &mut Builder| {
+        let dest = builder.new_register();
+        builder.push(Op::Unary { dest, op, a });
+        Operand::from(dest)
+    };
+
+    let whole = unary(UnaryOp::Trunc, exponent.clone(), builder);
+    let is_integer = binary(
+        BinaryOp::Compare(Comparison::Equal),
+        whole,
+        exponent.clone(),
+        builder,
+    );
+    let magnitude = unary(UnaryOp::Abs, base.clone(), builder);
+    let log_base = builder.new_register();
+    builder.push(Op::Select {
+        dest: log_base,
+        condition: is_integer,
+        then: magnitude,
+        otherwise: base.clone(),
+    });
+    let logarithm = unary(UnaryOp::Log, log_base.into(), builder);
+    let product = binary(BinaryOp::Mul, logarithm, exponent.clone(), builder);
+    let power = unary(UnaryOp::Exp, product, builder);
+    let is_zero = unary(UnaryOp::IsZero, exponent.clone(), builder);
+    let power_dest = builder.new_register();
+    builder.push(Op::Select {
+        dest: power_dest,
+        condition: is_zero,
+        then: Number::Int(1).into(),
+        otherwise: power,
+    });
+    let power = Operand::from(power_dest);
+
+    // the remainder is 1 for odd exponents, including negative ones
+    let parity = binary(BinaryOp::Mod, exponent, Number::Int(2).into(), builder);
+    let is_odd = binary(
+        BinaryOp::Compare(Comparison::Equal),
+        parity,
+        Number::Int(1).into(),
+        builder,
+    );
+    let is_negative = binary(
+        BinaryOp::Compare(Comparison::Less),
+        base,
+        Number::Int(0).into(),
+        builder,
+    );
+    let flips_sign = binary(BinaryOp::And, is_negative, is_odd, builder);
+    let negated = binary(BinaryOp::Sub, Number::Int(0).into(), power.clone(), builder);
+    builder.push(Op::Select {
+        dest,
+        condition: flips_sign,
+        then: negated,
+        otherwise: power,
+    });
+}
+
+/// Picks the register that receives the result of an operation.
+/// This is the destination if there is one, otherwise a new register.
+pub(crate) fn result_register(destination: Option<VReg>, builder: &mut Builder) -> VReg {
+    destination.unwrap_or_else(|| builder.new_register())
+}
+
+/// Lowers an expression and returns the operand that holds the result.
+///
+/// Constants are returned as numbers and variables as their register, so no operations are
+/// needed for them. Other results are placed in a new register.
+pub(crate) fn lower_expr(
+    expr: &Expr,
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<Operand> {
+    lower_expr_to(expr, None, builder, codegen)
+}
 
-/// Emits code that evaluates an expression and pushes the result onto the stack.
-pub(crate) fn generate_expr(
+/// Lowers an expression so that its result ends up in the given register.
+pub(crate) fn lower_expr_into(
     expr: &Expr,
-    stack: &mut Stack,
+    register: VReg,
+    builder: &mut Builder,
     codegen: &mut CodeGenerator,
-    pass: Pass,
 ) -> Result<()> {
+    let operand = lower_expr_to(expr, Some(register), builder, codegen)?;
+    if operand.register() != Some(register) {
+        builder.push(Op::Move {
+            dest: register,
+            source: operand,
+        });
+    }
+
+    Ok(())
+}
+
+/// Lowers an expression.
+/// If a destination is given, operations that compute a new value write it there directly. The
+/// destination is only written by the last operation, so it may be used by the expression.
+fn lower_expr_to(
+    expr: &Expr,
+    destination: Option<VReg>,
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<Operand> {
+    if codegen.lint_units {
+        lint_comparison(expr, codegen);
+    }
+
+    // fold constant subexpressions
+    if let Ok(value) = evaluate_constant(expr, codegen) {
+        return Ok(constant_operand(value).into());
+    }
+
     match expr {
         Expr::Identifier(identifier) => {
-            codegen.add_comment_line(format!("expr identifier {identifier:?}"));
+            let identifier_ref: &String = identifier.as_ref();
+            let register = builder
+                .locals
+                .get(identifier_ref)
+                .copied()
+                .ok_or_else(|| Error::UndefinedVariable(identifier.to_string()))?;
 
-            // Check if the identifier refers to a constant
-            if let Some(value) = codegen.get_constant(identifier) {
-                generate_expr(&Expr::Constant(value), stack, codegen, pass)?;
-                return Ok(());
-            }
+            Ok(register.into())
+        }
+        Expr::Constant(value) => Ok(constant_operand(*value).into()),
+        Expr::BinaryOp(left, op @ (BinaryOpcode::Conj | BinaryOpcode::Disj), right) => {
+            // Logical operators short-circuit: the right operand is only evaluated when the left
+            // operand does not already decide the result.
+            let name = match op {
+                BinaryOpcode::Conj => builder.new_logic("and"),
+                _ => builder.new_logic("or"),
+            };
+            let right_block = builder.new_block(name.clone());
+            let end_block = builder.new_block(format!("{name}_end"));
 
-            let identifier_ref: &String = identifier.as_ref();
-            if let Some(location) = stack.locals.get(identifier_ref) {
-                match location {
-                    Location::Register(register) => {
-                        // push the value of the register onto the stack
-                        stack_push!(codegen, *register);
-                    }
-                    Location::Stack(offset) => {
-                        let offset = -(*offset);
-                        // load the value value of the identifier from memory and push it onto the stack
-
-                        // adjust the stack pointer to be at the location of the local variable
-                        if offset != 1 {
-                            codegen.add_instruction(Instruction::from(Arithmetic::Subtract {
-                                register: Register::Sp,
-                                a: Register::Sp.into(),
-                                b: Number::Int(offset).into(),
-                            }));
-                            codegen.add_comment(format!(
-                                "retieve {identifier:?} from offset {offset}"
-                            ));
-                        } else {
-                            codegen.add_comment_line(format!(
-                                "retieve {identifier:?} from offset {offset}"
-                            ));
-                        }
+            // The result is built up in a register of its own, as the destination may be read by
+            // the right operand.
+            let register = builder.new_register();
 
-                        // peek the value from the stack
-                        codegen.add_instruction(Instruction::from(StackInstruction::Peek {
-                            register: Register::R0,
-                        }));
-
-                        if offset != 1 {
-                            // restore the stack pointer to its original value
-                            codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                                register: Register::Sp,
-                                a: Register::Sp.into(),
-                                b: Number::Int(offset).into(),
-                            }));
-                        }
-                        // push the value onto the stack
-                        stack_push!(codegen, Register::R0);
-                    }
-                }
-                Ok(())
-            } else {
-                Err(Error::UndefinedVariable(identifier.to_string()))
-            }
+            // normalize the operand to 0 or 1
+            let left = lower_expr(left, builder, codegen)?;
+            builder.push(Op::Unary {
+                dest: register,
+                op: UnaryOp::NotZero,
+                a: left,
+            });
+
+            // the register already holds the result if the right operand is skipped
+            let comparison = match op {
+                BinaryOpcode::Conj => Comparison::Equal,
+                _ => Comparison::NotEqual,
+            };
+            builder.terminate(Terminator::Branch {
+                condition: Condition::Compare {
+                    comparison,
+                    a: register.into(),
+                    b: Number::Int(0).into(),
+                },
+                then: end_block,
+                otherwise: right_block,
+            });
+
+            builder.switch_to(right_block);
+            let right = lower_expr(right, builder, codegen)?;
+            builder.push(Op::Unary {
+                dest: register,
+                op: UnaryOp::NotZero,
+                a: right,
+            });
+
+            builder.switch_to(end_block);
+
+            Ok(register.into())
         }
-        Expr::Constant(value) => {
-            codegen.add_comment_line(format!("expr constant {value:?}"));
+        Expr::BinaryOp(left, BinaryOpcode::Pow, right) => {
+            let base = lower_expr(left, builder, codegen)?;
 
-            match value {
-                Value::Integer(i) => {
-                    // push the integer onto the stack
-                    stack_push!(codegen, Number::Int(*i as i32));
+            // small integer powers are computed by repeated multiplication, which also works for
+            // negative bases
+            if let Ok(Value::Integer(exponent @ 0..=MAX_MULTIPLIED_POWER)) =
+                evaluate_constant(right, codegen)
+            {
+                if exponent == 0 {
+                    return Ok(Number::Int(1).into());
                 }
-                Value::Float(f) => {
-                    // push the float onto the stack
-                    stack_push!(codegen, Number::Float(*f as f32));
-                }
-                Value::Boolean(b) => {
-                    // push the boolean onto the stack
-                    stack_push!(codegen, Number::Int(if *b { 1 } else { 0 }));
+
+                let mut power = base.clone();
+                for i in 1..exponent {
+                    let register = if i + 1 == exponent {
+                        result_register(destination, builder)
+                    } else {
+                        builder.new_register()
+                    };
+                    builder.push(Op::Binary {
+                        dest: register,
+                        op: BinaryOp::Mul,
+                        a: power,
+                        b: base.clone(),
+                    });
+                    power = register.into();
                 }
+
+                return Ok(power);
             }
 
-            Ok(())
+            let exponent = lower_expr(right, builder, codegen)?;
+            let register = result_register(destination, builder);
+            lower_power(base, exponent, register, builder);
+
+            Ok(register.into())
         }
         Expr::BinaryOp(left, op, right) => {
-            codegen.add_comment_line(format!("expr binary op {op:?}"));
-
-            // recursively call `generate_expr` for the left and right operands
-            generate_expr(left, stack, codegen, pass)?;
-            generate_expr(right, stack, codegen, pass)?;
-
-            // pop the results of the left and right operands off the stack
-            stack_pop!(codegen, Register::R1);
-            stack_pop!(codegen, Register::R0);
-
-            // perform operation
-            match op {
-                ayysee_parser::ast::BinaryOpcode::Add => {
-                    codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                        register: Register::R0,
-                        a: Register::R0.into(),
-                        b: Register::R1.into(),
-                    }));
-                }
-                ayysee_parser::ast::BinaryOpcode::Sub => {
-                    codegen.add_instruction(Instruction::from(Arithmetic::Subtract {
-                        register: Register::R0,
-                        a: Register::R0.into(),
-                        b: Register::R1.into(),
-                    }));
-                }
-                ayysee_parser::ast::BinaryOpcode::Mul => {
-                    codegen.add_instruction(Instruction::from(Arithmetic::Multiply {
-                        register: Register::R0,
-                        a: Register::R0.into(),
-                        b: Register::R1.into(),
-                    }));
-                }
-                ayysee_parser::ast::BinaryOpcode::Div => {
-                    codegen.add_instruction(Instruction::from(Arithmetic::Divide {
-                        register: Register::R0,
-                        a: Register::R0.into(),
-                        b: Register::R1.into(),
-                    }));
+            let left = lower_expr(left, builder, codegen)?;
+            let right = lower_expr(right, builder, codegen)?;
+
+            let register = result_register(destination, builder);
+            builder.push(Op::Binary {
+                dest: register,
+                op: binary_op(*op),
+                a: left,
+                b: right,
+            });
+
+            Ok(register.into())
+        }
+        Expr::UnaryOp(op, operand) => {
+            let operand = lower_expr(operand, builder, codegen)?;
+            let register = result_register(destination, builder);
+
+            let op = match op {
+                // any non-zero value is truthy, so only 0 becomes 1
+                UnaryOpcode::Not => Op::Unary {
+                    dest: register,
+                    op: UnaryOp::IsZero,
+                    a: operand,
+                },
+                UnaryOpcode::Neg => Op::Binary {
+                    dest: register,
+                    op: BinaryOp::Sub,
+                    a: Number::Int(0).into(),
+                    b: operand,
+                },
+                // ~a = ~(a | 0)
+                UnaryOpcode::BitNot => Op::Binary {
+                    dest: register,
+                    op: BinaryOp::Nor,
+                    a: operand,
+                    b: Number::Int(0).into(),
+                },
+            };
+            builder.push(op);
+
+            Ok(register.into())
+        }
+        Expr::DeviceRead(device, device_variable) => {
+            let device = codegen.resolve_device(device)?;
+            let variable: &str = device_variable.as_ref();
+            let variable = DeviceVariable::from_str(variable)?;
+            let register = result_register(destination, builder);
+            builder.push(Op::LoadDevice {
+                dest: register,
+                device,
+                variable,
+            });
+
+            Ok(register.into())
+        }
+        Expr::SlotRead(device, slot, slot_variable) => {
+            let slot = lower_expr(slot, builder, codegen)?;
+            let device = codegen.resolve_device(device)?;
+            let variable: &str = slot_variable.as_ref();
+            let variable = SlotVariable::from_str(variable)?;
+            let register = result_register(destination, builder);
+            builder.push(Op::LoadSlot {
+                dest: register,
+                device,
+                slot,
+                variable,
+            });
+
+            Ok(register.into())
+        }
+        Expr::ReagentRead(device, reagent_mode, reagent) => {
+            let device = codegen.resolve_device(device)?;
+            let reagent_mode: &str = reagent_mode.as_ref();
+            let reagent_mode = ReagentMode::from_str(reagent_mode)?;
+            // reagents are identified by the hash of their name
+            let reagent_name: &str = reagent.as_ref();
+            let reagent_hash = Reagent::from_str(&hash(reagent_name).to_string())?;
+            let register = result_register(destination, builder);
+            builder.push(Op::LoadReagent {
+                dest: register,
+                device,
+                mode: reagent_mode,
+                reagent: reagent_hash,
+            });
+
+            Ok(register.into())
+        }
+        Expr::Select(condition, then, otherwise) => {
+            // selecting between 1 and 0 is the same as normalizing the condition
+            match (as_flag(then, codegen), as_flag(otherwise, codegen)) {
+                (Some(true), Some(false)) => {
+                    return match condition.as_ref() {
+                        Expr::BinaryOp(_, op, _) if invert_comparison(*op).is_some() => {
+                            lower_expr_to(condition, destination, builder, codegen)
+                        }
+                        _ => lower_flag(condition, false, destination, builder, codegen),
+                    };
                 }
-                ayysee_parser::ast::BinaryOpcode::Conj => todo!(),
-                ayysee_parser::ast::BinaryOpcode::Disj => todo!(),
-                ayysee_parser::ast::BinaryOpcode::Equals => {
-                    if let Pass::Second = pass {
-                        // Approach: have two sets of instructions that set r0 to either 0 or 1.
-                        // Branch to the appropriate set of instructions based on the result of the comparison.
-                        let target_line = codegen.instructions.len() + 2;
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchEqual {
-                            a: Register::R0.into(),
-                            b: Register::R1.into(),
-                            c: Number::Int(target_line as i32).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(1).into(),
-                        }));
-                    } else {
-                        // Reserve space for second pass by generating placeholder instructions
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchEqual {
-                            a: Register::R0.into(),
-                            b: Register::R1.into(),
-                            c: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                    }
+                (Some(false), Some(true)) => {
+                    return match condition.as_ref() {
+                        Expr::BinaryOp(left, op, right) if invert_comparison(*op).is_some() => {
+                            let inverted = invert_comparison(*op).unwrap();
+                            let condition = Expr::BinaryOp(left.clone(), inverted, right.clone());
+                            lower_expr_to(&condition, destination, builder, codegen)
+                        }
+                        _ => lower_flag(condition, true, destination, builder, codegen),
+                    };
                 }
+                _ => {}
+            }
 
-                ayysee_parser::ast::BinaryOpcode::NotEquals => todo!(),
-                ayysee_parser::ast::BinaryOpcode::Greater => {
-                    if let Pass::Second = pass {
-                        // Approach: have two sets of instructions that set r0 to either 0 or 1.
-                        // Branch to the appropriate set of instructions based on the result of the comparison.
-                        let target_line = codegen.instructions.len() + 2;
-                        codegen.add_instruction(Instruction::from(
-                            FlowControl::BranchGreaterThan {
-                                a: Register::R0.into(),
-                                b: Register::R1.into(),
-                                c: Number::Int(target_line as i32).into(),
-                            },
-                        ));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(1).into(),
-                        }));
-                    } else {
-                        // Reserve space for second pass by generating placeholder instructions
-                        codegen.add_instruction(Instruction::from(
-                            FlowControl::BranchGreaterThan {
-                                a: Register::R0.into(),
-                                b: Register::R1.into(),
-                                c: Number::Int(0).into(),
-                            },
-                        ));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                    }
-                }
-                ayysee_parser::ast::BinaryOpcode::GreaterEquals => todo!(),
-                ayysee_parser::ast::BinaryOpcode::Lower => {
-                    if let Pass::Second = pass {
-                        // Approach: have two sets of instructions that set r0 to either 0 or 1.
-                        // Branch to the appropriate set of instructions based on the result of the comparison.
-                        let target_line = codegen.instructions.len() + 2;
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchLessThan {
-                            a: Register::R0.into(),
-                            b: Register::R1.into(),
-                            c: Number::Int(target_line as i32).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(1).into(),
-                        }));
-                    } else {
-                        // Reserve space for second pass by generating placeholder instructions
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchLessThan {
-                            a: Register::R0.into(),
-                            b: Register::R1.into(),
-                            c: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                        codegen.add_instruction(Instruction::from(Arithmetic::Add {
-                            register: Register::R0,
-                            a: Number::Int(0).into(),
-                            b: Number::Int(0).into(),
-                        }));
-                    }
+            // `select` evaluates both branches, so only the chosen one may have side effects
+            if has_side_effects(then) || has_side_effects(otherwise) {
+                let register = result_register(destination, builder);
+                let name = builder.new_if();
+                let then_block = builder.new_block(name.clone());
+                let else_block = builder.new_block(format!("{name}_else"));
+                let end_block = builder.new_block(format!("{name}_end"));
+
+                let condition = lower_condition(condition, builder, codegen)?;
+                builder.terminate(Terminator::Branch {
+                    condition,
+                    then: then_block,
+                    otherwise: else_block,
+                });
+
+                builder.switch_to(then_block);
+                lower_expr_into(then, register, builder, codegen)?;
+                builder.terminate(Terminator::Jump(end_block));
+
+                builder.switch_to(else_block);
+                lower_expr_into(otherwise, register, builder, codegen)?;
+
+                builder.switch_to(end_block);
+
+                return Ok(register.into());
+            }
+
+            let condition = lower_expr(condition, builder, codegen)?;
+            let then = lower_expr(then, builder, codegen)?;
+            let otherwise = lower_expr(otherwise, builder, codegen)?;
+
+            let register = result_register(destination, builder);
+            builder.push(Op::Select {
+                dest: register,
+                condition,
+                then,
+                otherwise,
+            });
+
+            Ok(register.into())
+        }
+        Expr::Connected(device) => {
+            let device = codegen.resolve_device(device)?;
+            let register = result_register(destination, builder);
+            builder.push(Op::Connected {
+                dest: register,
+                device,
+            });
+
+            Ok(register.into())
+        }
+        Expr::BatchRead(device_type, device_variable, batch_mode) => {
+            let type_hash = resolve_type_hash(device_type, codegen)?;
+            let variable: &str = device_variable.as_ref();
+            let variable = DeviceVariable::from_str(variable)?;
+            let batch_mode: &str = batch_mode.as_ref();
+            let batch_mode = BatchMode::from_str(batch_mode)?;
+            let register = result_register(destination, builder);
+            builder.push(Op::LoadBatch {
+                dest: register,
+                type_hash,
+                variable,
+                mode: batch_mode,
+            });
+
+            Ok(register.into())
+        }
+        Expr::Hash(_) => {
+            let value = evaluate_constant(expr, codegen)?;
+            Ok(constant_operand(value).into())
+        }
+        Expr::Call(identifier, arguments) => {
+            let name: &str = identifier.as_ref();
+            if let Some(intrinsic) = Intrinsic::from_name(name) {
+                return lower_intrinsic(
+                    intrinsic,
+                    identifier,
+                    arguments,
+                    destination,
+                    builder,
+                    codegen,
+                );
+            }
+
+            if let Some(signature) = codegen.get_function(identifier) {
+                if !signature.returns_value {
+                    return Err(Error::NoReturnValue(identifier.to_string()));
                 }
-                ayysee_parser::ast::BinaryOpcode::LowerEquals => todo!(),
             }
 
-            // push the result of the operation onto the stack
-            stack_push!(codegen, Register::R0);
+            let register = result_register(destination, builder);
+            lower_function_call(identifier, arguments, Some(register), builder, codegen)?;
+
+            Ok(register.into())
+        }
+    }
+}
+
+/// Lowers an expression into the condition of a branch, which holds if the expression is
+/// non-zero.
+pub(crate) fn lower_condition(
+    expr: &Expr,
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<Condition> {
+    match expr {
+        // connection checks can branch on the device directly
+        Expr::Connected(device) => {
+            let device = codegen.resolve_device(device)?;
+            return Ok(Condition::DeviceSet(device));
+        }
+        Expr::UnaryOp(UnaryOpcode::Not, operand) if evaluate_constant(expr, codegen).is_err() => {
+            return Ok(lower_condition(operand, builder, codegen)?.invert());
+        }
+        // comparisons branch on their operands directly
+        Expr::BinaryOp(left, op, right)
+            if invert_comparison(*op).is_some() && evaluate_constant(expr, codegen).is_err() =>
+        {
+            if codegen.lint_units {
+                lint_comparison(expr, codegen);
+            }
+
+            let a = lower_expr(left, builder, codegen)?;
+            let b = lower_expr(right, builder, codegen)?;
+            return Ok(Condition::Compare {
+                comparison: comparison(*op),
+                a,
+                b,
+            });
+        }
+        _ => {}
+    }
+
+    let value = lower_expr(expr, builder, codegen)?;
+    Ok(Condition::Compare {
+        comparison: Comparison::NotEqual,
+        a: value,
+        b: Number::Int(0).into(),
+    })
+}
+
+/// Resolves the device type of a batch operation to its hash.
+/// Names that are not constants are treated as prefab names and hashed.
+pub(crate) fn resolve_type_hash(expr: &Expr, codegen: &CodeGenerator) -> Result<TypeHash> {
+    let hash = match expr {
+        Expr::Identifier(identifier) if codegen.get_constant(identifier).is_none() => {
+            let name: &str = identifier.as_ref();
+            hash(name).into()
+        }
+        Expr::Identifier(identifier) => match evaluate_constant(expr, codegen)? {
+            Value::Integer(hash) => hash,
+            _ => return Err(Error::InvalidDeviceType(identifier.to_string())),
+        },
+        _ => match evaluate_constant(expr, codegen)? {
+            Value::Integer(hash) => hash,
+            _ => return Err(Error::InvalidDeviceType(format!("{expr:?}"))),
+        },
+    };
+
+    Ok(TypeHash::from_str(&hash.to_string())?)
+}
+
+/// Checks whether an expression is a constant 1 or 0.
+fn as_flag(expr: &Expr, codegen: &CodeGenerator) -> Option<bool> {
+    match evaluate_constant(expr, codegen) {
+        Ok(Value::Integer(1) | Value::Boolean(true)) => Some(true),
+        Ok(Value::Integer(0) | Value::Boolean(false)) => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether evaluating an expression does more than compute a value from constants and
+/// variables: calling a function, accessing a device or drawing a random number.
+fn has_side_effects(expr: &Expr) -> bool {
+    match expr {
+        Expr::Constant(_) | Expr::Identifier(_) | Expr::Hash(_) => false,
+        Expr::BinaryOp(left, _, right) => has_side_effects(left) || has_side_effects(right),
+        Expr::UnaryOp(_, operand) => has_side_effects(operand),
+        Expr::Call(identifier, arguments) => match Intrinsic::from_name(identifier.as_ref()) {
+            Some(Intrinsic::Rand) | None => true,
+            Some(_) => arguments.iter().any(|argument| has_side_effects(argument)),
+        },
+        Expr::Select(condition, then, otherwise) => {
+            has_side_effects(condition) || has_side_effects(then) || has_side_effects(otherwise)
+        }
+        Expr::DeviceRead(..)
+        | Expr::BatchRead(..)
+        | Expr::SlotRead(..)
+        | Expr::ReagentRead(..)
+        | Expr::Connected(_) => true,
+    }
+}
+
+/// Returns the comparison that is true exactly when the given one is false.
+pub(crate) fn invert_comparison(op: BinaryOpcode) -> Option<BinaryOpcode> {
+    match op {
+        BinaryOpcode::Equals => Some(BinaryOpcode::NotEquals),
+        BinaryOpcode::NotEquals => Some(BinaryOpcode::Equals),
+        BinaryOpcode::Greater => Some(BinaryOpcode::LowerEquals),
+        BinaryOpcode::GreaterEquals => Some(BinaryOpcode::Lower),
+        BinaryOpcode::Lower => Some(BinaryOpcode::GreaterEquals),
+        BinaryOpcode::LowerEquals => Some(BinaryOpcode::Greater),
+        _ => None,
+    }
+}
+
+/// Lowers an expression to 1 if it is non-zero and 0 otherwise, or the opposite if `inverted`
+/// is set.
+fn lower_flag(
+    expr: &Expr,
+    inverted: bool,
+    destination: Option<VReg>,
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<Operand> {
+    let a = lower_expr(expr, builder, codegen)?;
+    let register = result_register(destination, builder);
+
+    let op = if inverted {
+        UnaryOp::IsZero
+    } else {
+        UnaryOp::NotZero
+    };
+    builder.push(Op::Unary {
+        dest: register,
+        op,
+        a,
+    });
+
+    Ok(register.into())
+}
+
+/// Converts a comparison operator into the comparison of the IR.
+pub(crate) fn comparison(op: BinaryOpcode) -> Comparison {
+    match op {
+        BinaryOpcode::Equals => Comparison::Equal,
+        BinaryOpcode::NotEquals => Comparison::NotEqual,
+        BinaryOpcode::Greater => Comparison::Greater,
+        BinaryOpcode::GreaterEquals => Comparison::GreaterOrEqual,
+        BinaryOpcode::Lower => Comparison::Less,
+        BinaryOpcode::LowerEquals => Comparison::LessOrEqual,
+        _ => unreachable!("{op:?} is not a comparison"),
+    }
+}
+
+/// Converts a binary operator into the operation of the IR.
+/// Logical operators and exponentiation need more than one operation and are not supported.
+pub(crate) fn binary_op(op: BinaryOpcode) -> BinaryOp {
+    match op {
+        BinaryOpcode::Add => BinaryOp::Add,
+        BinaryOpcode::Sub => BinaryOp::Sub,
+        BinaryOpcode::Mul => BinaryOp::Mul,
+        BinaryOpcode::Div => BinaryOp::Div,
+        BinaryOpcode::Rem => BinaryOp::Mod,
+        BinaryOpcode::BitAnd => BinaryOp::And,
+        BinaryOpcode::BitOr => BinaryOp::Or,
+        BinaryOpcode::BitXor => BinaryOp::Xor,
+        BinaryOpcode::ShiftLeft => BinaryOp::ShiftLeft,
+        BinaryOpcode::ShiftRight => BinaryOp::ShiftRightArithmetic,
+        BinaryOpcode::ShiftRightLogical => BinaryOp::ShiftRightLogical,
+        BinaryOpcode::Equals
+        | BinaryOpcode::NotEquals
+        | BinaryOpcode::Greater
+        | BinaryOpcode::GreaterEquals
+        | BinaryOpcode::Lower
+        | BinaryOpcode::LowerEquals => BinaryOp::Compare(comparison(op)),
+        BinaryOpcode::Conj | BinaryOpcode::Disj | BinaryOpcode::Pow => {
+            unreachable!("{op:?} is handled separately")
+        }
+    }
+}
+
+/// Converts a constant value into an immediate operand.
+/// IC10 numbers are doubles, so integers outside of the range of `i32` are still exact up to
+/// 2^53.
+pub(crate) fn constant_operand(value: Value) -> Number {
+    match value {
+        Value::Integer(i) => match i32::try_from(i) {
+            Ok(i) => Number::Int(i),
+            Err(_) => Number::Float(i as f64),
+        },
+        Value::Float(f) => Number::Float(f),
+        Value::Quantity(value, unit) => Number::Float(unit.normalize(value)),
+        Value::Boolean(b) => Number::Int(if b { 1 } else { 0 }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::compile;
 
-            Ok(())
+    #[test]
+    fn test_comparisons_and_logic() {
+        // a comparison that is stored becomes a single set instruction
+        let program = compile("fn main() { d0.On = d0.Temperature > 300; }");
+        assert_eq!(
+            program.lines[3..6],
+            ["l r4 d0 Temperature", "sgt r4 r4 300", "s d0 On r4"]
+        );
+
+        let program = compile(
+            "fn main() {
+                let t = d0.Temperature;
+                d0.On = t > 300;
+                d0.Open = t <= 10 && t != 5;
+                d0.Lock = !(t == 2) || t >= 7;
+            }",
+        );
+        for opcode in ["sgt", "sle", "sne", "seq", "seqz", "sge"] {
+            assert!(program.uses(opcode), "{opcode} is used");
+        }
+
+        let run = |t| program.writes(&[("d0 Temperature", t)]);
+        assert_eq!(run(301.0), ["d0 On 1", "d0 Open 0", "d0 Lock 1"]);
+        assert_eq!(run(5.0), ["d0 On 0", "d0 Open 0", "d0 Lock 1"]);
+        assert_eq!(run(4.0), ["d0 On 0", "d0 Open 1", "d0 Lock 1"]);
+        assert_eq!(run(2.0), ["d0 On 0", "d0 Open 1", "d0 Lock 0"]);
+    }
+
+    #[test]
+    fn test_binary_operators() {
+        let program = compile(
+            "fn main() {
+                let a = d0.Setting;
+                let b = d0.Mode;
+                d1.Setting = a % b;
+                d1.Mode = a & b;
+                d1.On = a | b;
+                d1.Open = a ^ b;
+                d1.Lock = ~a;
+                d2.Setting = a << b;
+                d2.Mode = a >> b;
+                d2.On = a >>> b;
+                d2.Open = -a;
+            }",
+        );
+        for instruction in [
+            "mod r6 r4 r5",
+            "and r6 r4 r5",
+            "or r6 r4 r5",
+            "xor r6 r4 r5",
+            "nor r6 r4 0",
+            "sll r6 r4 r5",
+            "sra r6 r4 r5",
+            "srl r5 r4 r5",
+            "sub r4 0 r4",
+        ] {
+            assert!(program.contains(instruction), "{instruction} is missing");
+        }
+
+        let run = |a, b| program.writes(&[("d0 Setting", a), ("d0 Mode", b)]);
+        assert_eq!(
+            run(12.0, 2.0),
+            [
+                "d1 Setting 0",
+                "d1 Mode 0",
+                "d1 On 14",
+                "d1 Open 14",
+                "d1 Lock -13",
+                "d2 Setting 48",
+                "d2 Mode 3",
+                "d2 On 3",
+                "d2 Open -12",
+            ]
+        );
+        // `%` of a positive divisor is never negative, `>>` keeps the sign and `>>>` shifts in
+        // zeros, which makes a negative number positive
+        let writes = run(-7.0, 3.0);
+        assert_eq!(
+            writes[..7],
+            [
+                "d1 Setting 2",
+                "d1 Mode 1",
+                "d1 On -5",
+                "d1 Open -6",
+                "d1 Lock 6",
+                "d2 Setting -56",
+                "d2 Mode -1",
+            ]
+        );
+        assert!(!writes[7].contains('-'), "{}", writes[7]);
+
+        // as in the game, a negative remainder has the divisor added to it even when the divisor
+        // is negative
+        let program = compile("fn main() { d1.Setting = d0.Setting % d0.Mode; }");
+        let remainder = |a, b| program.writes(&[("d0 Setting", a), ("d0 Mode", b)]);
+        assert_eq!(remainder(7.0, -3.0), ["d1 Setting 1"]);
+        assert_eq!(remainder(-7.0, -3.0), ["d1 Setting -4"]);
+    }
+
+    #[test]
+    fn test_runtime_powers() {
+        let program = compile("fn main() { d1.Setting = d0.Setting ** d0.Mode; }");
+        let power = |base, exponent| {
+            let writes = program.writes(&[("d0 Setting", base), ("d0 Mode", exponent)]);
+            let value = writes[0].strip_prefix("d1 Setting ").unwrap();
+            value.parse::<f64>().unwrap()
+        };
+
+        // `exp` and `log` are not exact
+        for (base, exponent, expected) in [
+            (2.0, 10.0, 1024.0),
+            (4.0, 0.5, 2.0),
+            (2.0, -1.0, 0.5),
+            (-2.0, 3.0, -8.0),
+            (-2.0, 2.0, 4.0),
+            (-2.0, -1.0, -0.5),
+            (0.0, 2.0, 0.0),
+            (0.0, 0.0, 1.0),
+            (-3.0, 0.0, 1.0),
+        ] {
+            let result = power(base, exponent);
+            assert!(
+                (result - expected).abs() < 1e-9,
+                "{base} ** {exponent} = {result}"
+            );
         }
-        Expr::UnaryOp(op, _operand) => {
-            codegen.add_comment_line(format!("expr unary op {op:?}"));
+        assert_eq!(power(0.0, -1.0), f64::INFINITY);
+        assert!(power(-8.0, 0.5).is_nan());
+    }
+
+    #[test]
+    fn test_select() {
+        // both arms are computed and one of them is picked
+        let program = compile(
+            "fn main() {
+                let a = d0.Setting;
+                d1.Setting = if a > 0 { a * 2 } else { 5 };
+            }",
+        );
+        assert_eq!(
+            program.lines[4..8],
+            [
+                "sgt r5 r4 0",
+                "mul r4 r4 2",
+                "select r4 r5 r4 5",
+                "s d1 Setting r4"
+            ]
+        );
+        let run = |a| program.writes(&[("d0 Setting", a)]);
+        assert_eq!(run(3.0), ["d1 Setting 6"]);
+        assert_eq!(run(-3.0), ["d1 Setting 5"]);
+
+        // only the chosen function may be called
+        let program = compile(
+            "fn up(x) { d2.Setting = x; return x + 1; }
+            fn down(x) { d3.Setting = x; return x - 1; }
+            fn main() {
+                let a = d0.Setting;
+                d1.Setting = if a > 0 { up(a) } else { down(a) };
+            }",
+        );
+        assert!(program.contains("blez r4 main.if_0_else"));
+        assert!(!program.uses("select"));
+        let run = |a| program.writes(&[("d0 Setting", a)]);
+        assert_eq!(run(3.0), ["d2 Setting 3", "d1 Setting 4"]);
+        assert_eq!(run(-3.0), ["d3 Setting -3", "d1 Setting -4"]);
 
-            // call `generate_expr` for the operand
-            // pop the result of the operand off the stack and perform the operation
-            todo!();
+        // device reads and random numbers are not computed speculatively either
+        for arm in [
+            "d2.Setting",
+            "rand()",
+            "batch(StructureGasSensor).Temperature.Average",
+        ] {
+            let program = compile(&format!(
+                "fn main() {{ d1.Setting = if d0.On {{ {arm} }} else {{ 0 }}; }}"
+            ));
+            assert!(!program.uses("select"), "{arm}");
         }
     }
+
+    #[test]
+    fn test_large_integers() {
+        // numbers that do not fit in 32 bits are written out exactly
+        let program = compile(
+            "const Big = 0x7FFFFFFF + 1;
+            fn main() {
+                d1.Setting = 0xFFFFFFFF;
+                d1.On = 123456789012;
+                d1.Lock = Big;
+                d1.Mode = -0x80000001;
+            }",
+        );
+        for number in ["4294967295", "123456789012", "2147483648", "-2147483649"] {
+            assert!(
+                program.contains(&format!("move r1 {number}")),
+                "{number} is missing from {:?}",
+                program.lines
+            );
+        }
+
+        assert_eq!(
+            program.writes(&[]),
+            [
+                "d1 Setting 4294967295",
+                "d1 On 123456789012",
+                "d1 Lock 2147483648",
+                "d1 Mode -2147483649",
+            ]
+        );
+    }
 }
@@ -0,0 +1,273 @@
+use crate::{
+    builder::Builder,
+    codegen::CodeGenerator,
+    constant::evaluate_constant,
+    error::{Error, Result},
+    expr::{lower_expr, result_register},
+};
+use ayysee_ir::{BinaryOp, Op, Operand, UnaryOp, VReg};
+use ayysee_parser::ast::{Expr, Identifier, Value};
+
+/// Functions that are built into the language.
+/// Each of them lowers to a single operation, so their names cannot be used for user
+/// defined functions.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Intrinsic {
+    Abs,
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Exp,
+    Log,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+    Min,
+    Max,
+    Mod,
+    Rand,
+}
+
+impl Intrinsic {
+    /// Finds the intrinsic with the given name.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "abs" => Some(Intrinsic::Abs),
+            "sqrt" => Some(Intrinsic::Sqrt),
+            "sin" => Some(Intrinsic::Sin),
+            "cos" => Some(Intrinsic::Cos),
+            "tan" => Some(Intrinsic::Tan),
+            "asin" => Some(Intrinsic::Asin),
+            "acos" => Some(Intrinsic::Acos),
+            "atan" => Some(Intrinsic::Atan),
+            "exp" => Some(Intrinsic::Exp),
+            "log" => Some(Intrinsic::Log),
+            "floor" => Some(Intrinsic::Floor),
+            "ceil" => Some(Intrinsic::Ceil),
+            "round" => Some(Intrinsic::Round),
+            "trunc" => Some(Intrinsic::Trunc),
+            "min" => Some(Intrinsic::Min),
+            "max" => Some(Intrinsic::Max),
+            "mod" => Some(Intrinsic::Mod),
+            "rand" => Some(Intrinsic::Rand),
+            _ => None,
+        }
+    }
+
+    /// The number of arguments the intrinsic takes.
+    pub(crate) fn arity(self) -> usize {
+        match self {
+            Intrinsic::Rand => 0,
+            Intrinsic::Min | Intrinsic::Max | Intrinsic::Mod => 2,
+            _ => 1,
+        }
+    }
+
+    /// Computes the intrinsic at compile time.
+    /// Returns `None` for intrinsics whose result is only known at runtime.
+    pub(crate) fn evaluate(self, arguments: &[f64]) -> Option<f64> {
+        let a = || arguments[0];
+        let b = || arguments[1];
+
+        let result = match self {
+            Intrinsic::Abs => a().abs(),
+            Intrinsic::Sqrt => a().sqrt(),
+            Intrinsic::Sin => a().sin(),
+            Intrinsic::Cos => a().cos(),
+            Intrinsic::Tan => a().tan(),
+            Intrinsic::Asin => a().asin(),
+            Intrinsic::Acos => a().acos(),
+            Intrinsic::Atan => a().atan(),
+            Intrinsic::Exp => a().exp(),
+            Intrinsic::Log => a().ln(),
+            Intrinsic::Floor => a().floor(),
+            Intrinsic::Ceil => a().ceil(),
+            // IC10 rounds halfway cases to the nearest even number
+            Intrinsic::Round => a().round_ties_even(),
+            Intrinsic::Trunc => a().trunc(),
+            Intrinsic::Min => a().min(b()),
+            Intrinsic::Max => a().max(b()),
+            Intrinsic::Mod => {
+                let remainder = a() % b();
+                if remainder < 0.0 {
+                    remainder + b()
+                } else {
+                    remainder
+                }
+            }
+            Intrinsic::Rand => return None,
+        };
+
+        result.is_finite().then_some(result)
+    }
+
+    /// Whether the result is always a whole number.
+    pub(crate) fn is_integral(self) -> bool {
+        matches!(
+            self,
+            Intrinsic::Floor | Intrinsic::Ceil | Intrinsic::Round | Intrinsic::Trunc
+        )
+    }
+
+    /// Whether the result is a whole number if all of the arguments are.
+    pub(crate) fn preserves_integers(self) -> bool {
+        matches!(
+            self,
+            Intrinsic::Abs | Intrinsic::Min | Intrinsic::Max | Intrinsic::Mod
+        )
+    }
+
+    /// Creates the operation that computes the intrinsic.
+    /// `arguments` must contain exactly `arity` operands.
+    fn op(self, dest: VReg, mut arguments: Vec<Operand>) -> Op {
+        let unary = |op| Op::Unary {
+            dest,
+            op,
+            a: arguments[0].clone(),
+        };
+
+        let binary = match self {
+            Intrinsic::Min => BinaryOp::Min,
+            Intrinsic::Max => BinaryOp::Max,
+            Intrinsic::Mod => BinaryOp::Mod,
+            Intrinsic::Abs => return unary(UnaryOp::Abs),
+            Intrinsic::Sqrt => return unary(UnaryOp::Sqrt),
+            Intrinsic::Sin => return unary(UnaryOp::Sin),
+            Intrinsic::Cos => return unary(UnaryOp::Cos),
+            Intrinsic::Tan => return unary(UnaryOp::Tan),
+            Intrinsic::Asin => return unary(UnaryOp::Asin),
+            Intrinsic::Acos => return unary(UnaryOp::Acos),
+            Intrinsic::Atan => return unary(UnaryOp::Atan),
+            Intrinsic::Exp => return unary(UnaryOp::Exp),
+            Intrinsic::Log => return unary(UnaryOp::Log),
+            Intrinsic::Floor => return unary(UnaryOp::Floor),
+            Intrinsic::Ceil => return unary(UnaryOp::Ceil),
+            Intrinsic::Round => return unary(UnaryOp::Round),
+            Intrinsic::Trunc => return unary(UnaryOp::Trunc),
+            Intrinsic::Rand => return Op::Random { dest },
+        };
+
+        let b = arguments
+            .pop()
+            .expect("binary intrinsics take two arguments");
+        let a = arguments
+            .pop()
+            .expect("binary intrinsics take two arguments");
+        Op::Binary {
+            dest,
+            op: binary,
+            a,
+            b,
+        }
+    }
+}
+
+/// Checks that no boolean constant is passed to an intrinsic, as they all operate on numbers.
+pub(crate) fn check_argument_types(
+    identifier: &Identifier,
+    arguments: &[Box<Expr>],
+    codegen: &CodeGenerator,
+) -> Result<()> {
+    for argument in arguments {
+        if let Ok(Value::Boolean(_)) = evaluate_constant(argument, codegen) {
+            return Err(Error::InvalidArgumentType {
+                function: identifier.to_string(),
+                argument: format!("{argument:?}"),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Lowers a call of an intrinsic and returns the operand that holds the result.
+pub(crate) fn lower_intrinsic(
+    intrinsic: Intrinsic,
+    identifier: &Identifier,
+    arguments: &[Box<Expr>],
+    destination: Option<VReg>,
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<Operand> {
+    if intrinsic.arity() != arguments.len() {
+        return Err(Error::WrongArgumentCount {
+            function: identifier.to_string(),
+            expected: intrinsic.arity(),
+            found: arguments.len(),
+        });
+    }
+
+    check_argument_types(identifier, arguments, codegen)?;
+
+    let mut operands = Vec::new();
+    for argument in arguments {
+        operands.push(lower_expr(argument, builder, codegen)?);
+    }
+
+    let dest = result_register(destination, builder);
+    builder.push(intrinsic.op(dest, operands));
+
+    Ok(dest.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        testing::{compile, compile_error},
+    };
+
+    #[test]
+    fn test_intrinsics() {
+        // every intrinsic is a single instruction of the same name
+        for name in [
+            "abs", "sqrt", "sin", "cos", "tan", "asin", "acos", "atan", "exp", "log", "floor",
+            "ceil", "round", "trunc",
+        ] {
+            let program = compile(&format!("fn main() {{ d1.Setting = {name}(d0.Setting); }}"));
+            assert_eq!(
+                program.lines[3..6],
+                [
+                    "l r4 d0 Setting",
+                    &format!("{name} r4 r4"),
+                    "s d1 Setting r4"
+                ]
+            );
+        }
+        for name in ["min", "max", "mod"] {
+            let program = compile(&format!(
+                "fn main() {{ d1.Setting = {name}(d0.Setting, 2); }}"
+            ));
+            assert_eq!(program.lines[4], format!("{name} r4 r4 2"));
+        }
+        let program = compile("fn main() { d1.Setting = rand(); }");
+        assert_eq!(program.lines[3..5], ["rand r4", "s d1 Setting r4"]);
+
+        // constant arguments are computed at compile time
+        let program = compile("fn main() { d1.Setting = sqrt(16) + max(2, 3) + mod(-1, 4); }");
+        assert_eq!(program.lines[3], "move r1 10");
+        assert_eq!(program.writes(&[]), ["d1 Setting 10"]);
+
+        assert!(matches!(
+            compile_error("fn main() { d1.Setting = min(1); }"),
+            Error::WrongArgumentCount {
+                expected: 2,
+                found: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            compile_error("fn main() { d1.Setting = abs(true); }"),
+            Error::InvalidArgumentType { .. }
+        ));
+        assert!(matches!(
+            compile_error("fn abs(x) { return x; } fn main() {}"),
+            Error::ReservedName(name) if name == "abs"
+        ));
+    }
+}
@@ -1,64 +1,244 @@
+use ayysee_ir::{Op, Terminator};
+use ayysee_parser::ast::Statement;
 use stationeers_mips::types::Register;
 
 use crate::{
+    builder::{Builder, TOP_LEVEL},
     codegen::CodeGenerator,
+    emit::emit_program,
     error::{Error, Result},
-    stack::Stack,
-    statement::generate_statement,
+    reachability::eliminate_dead_code,
+    relocation::{label_lines, relocate},
+    statement::{declare_function, lower_function, lower_statement},
 };
 
+pub mod builder;
 pub mod codegen;
+pub mod constant;
+pub mod emit;
+#[cfg(test)]
+mod emulator;
 pub mod error;
 pub mod expr;
+pub mod intrinsics;
+pub mod peephole;
+pub mod reachability;
+pub mod regalloc;
+pub mod relocation;
 pub mod stack;
 pub mod statement;
+#[cfg(test)]
+mod testing;
+pub mod units;
 pub mod util;
 
-#[derive(Copy, Clone, Debug)]
-/// The pass of the compiler.
-/// Multiple passes are used so that the compiler can resolve forward references.
-enum Pass {
-    /// The first pass, which resolves forward references.
-    /// Code will be generated to reserve space for instructions that come later.
-    First,
-    /// The second pass, which generates the actual code using the information from the first pass.
-    Second,
-}
-
 #[derive(Copy, Clone, Debug)]
 enum Location {
-    /// A position on the stack
+    /// A slot on the stack, counted from the base of the current frame
     Stack(i32),
     /// A register
     Register(Register),
 }
 
-/// Converts an entire program into MIPS assembly code.
-/// This function is the entry point for the code generation and handles the
-/// initial setup of the stack frame and code generator.
+/// The behaviour of the program once `main` returns.
+#[derive(Copy, Clone, Debug, Default)]
+pub enum Termination {
+    /// Stop execution using `hcf`. The IC housing will show an error state.
+    #[default]
+    Halt,
+    /// Jump back to the first line, running the top-level initialisation and `main` again.
+    Restart,
+}
+
+/// How `///` comments on functions and aliases appear in the generated code.
+#[derive(Copy, Clone, Debug, Default)]
+pub enum DocComments {
+    /// Leave them out, as comments take up lines.
+    #[default]
+    Omit,
+    /// Emit each comment on its own line before the function or alias.
+    Lines,
+    /// Append the comments to the line of the function label or alias.
+    Trailing,
+}
+
+/// Options that control how a program is compiled.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// What to do once `main` returns.
+    pub termination: Termination,
+    /// Warn about comparisons between temperatures and pressures.
+    pub lint_units: bool,
+    /// How doc comments are emitted.
+    pub doc_comments: DocComments,
+    /// Jump to labels by name instead of by line number.
+    /// IC10 resolves the names itself, which keeps the output readable.
+    pub keep_labels: bool,
+}
+
+/// Converts an entire program into MIPS assembly code using the default options.
 pub fn generate_program(program: ayysee_parser::ast::Program) -> Result<String> {
+    generate_program_with_options(program, &Options::default())
+}
+
+/// Converts an entire program into MIPS assembly code.
+/// This function is the entry point for the code generation.
+///
+/// The program is first lowered into the IR, from which the MIPS code is emitted. The generated
+/// program starts with the top-level statements (aliases, constants, etc.), followed by a call to
+/// `main` and the configured termination behaviour. Function bodies are placed after that so that
+/// execution never falls into them. Jumps refer to labels until the instructions have been
+/// cleaned up by the peephole optimiser, after which the labels are resolved to line numbers
+/// unless they are kept.
+pub fn generate_program_with_options(
+    program: ayysee_parser::ast::Program,
+    options: &Options,
+) -> Result<String> {
+    let mut codegen = CodeGenerator::new();
+    codegen.lint_units = options.lint_units;
+    codegen.doc_comments = options.doc_comments;
+
+    let ir = lower_program(&program, &mut codegen, options)?;
+    emit_program(&ir, &mut codegen);
+    codegen.optimize();
+    if options.keep_labels {
+        label_lines(&codegen.instructions)?;
+    } else {
+        relocate(&mut codegen.instructions)?;
+    }
+
+    Ok(codegen.get_code())
+}
+
+/// Lowers an entire program into the IR without emitting any MIPS code.
+pub fn generate_ir(
+    program: ayysee_parser::ast::Program,
+    options: &Options,
+) -> Result<ayysee_ir::Program> {
     let mut codegen = CodeGenerator::new();
-    let mut stack = Stack::new();
+    codegen.lint_units = options.lint_units;
+
+    lower_program(&program, &mut codegen, options)
+}
+
+/// Lowers the program: the top-level initialisation, which ends with the call to `main` and the
+/// termination sequence, and each of the functions. Code that can never run is removed.
+fn lower_program(
+    program: &ayysee_parser::ast::Program,
+    codegen: &mut CodeGenerator,
+    options: &Options,
+) -> Result<ayysee_ir::Program> {
+    let (functions, initialisation): (Vec<_>, Vec<_>) = program
+        .statements
+        .iter()
+        .partition(|statement| matches!(statement, Statement::Function { .. }));
 
-    for statement in &program.statements {
-        generate_statement(statement, &mut stack, &mut codegen, Pass::First)?;
+    for statement in &functions {
+        declare_function(statement, codegen)?;
     }
 
-    codegen.clear_first_pass();
-    stack.clear();
+    let mut builder = Builder::new();
+    builder.start_function(TOP_LEVEL.to_string(), false);
+    for statement in initialisation {
+        lower_statement(statement, &mut builder, codegen)?;
+    }
 
-    for statement in &program.statements {
-        generate_statement(statement, &mut stack, &mut codegen, Pass::Second)?;
+    // call main
+    let main = codegen
+        .get_function(&"main".into())
+        .ok_or(Error::UndefinedMain)?;
+    if main.parameters != 0 {
+        return Err(Error::WrongArgumentCount {
+            function: "main".to_string(),
+            expected: 0,
+            found: main.parameters,
+        });
     }
+    builder.push(Op::Call {
+        dest: None,
+        function: "main".to_string(),
+        arguments: Vec::new(),
+    });
+
+    // main has returned
+    builder.terminate(match options.termination {
+        Termination::Halt => Terminator::Halt,
+        Termination::Restart => Terminator::Restart,
+    });
+    let init = builder.finish_function();
 
-    // ensure the existance of a main function
-    if !codegen.has_label("main") {
-        return Err(Error::UndefinedMain);
+    let functions = functions
+        .into_iter()
+        .map(|statement| lower_function(statement, &mut builder, codegen))
+        .collect::<Result<_>>()?;
+
+    // constants take up no lines, but an unused one may be a typo
+    for constant in codegen.unused_constants() {
+        tracing::warn!("constant {constant} is never used");
     }
-    // Add instructions to exit program
-    // let last_line = codegen.instructions.len() as i32 + 2;
-    // Add instructions to call main function
-    let _main_line = codegen.labels.get("main").ok_or(Error::UndefinedMain)?;
 
-    Ok(codegen.get_code())
+    let mut program = ayysee_ir::Program { init, functions };
+    eliminate_dead_code(&mut program);
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        emulator::Exit,
+        testing::{compile, compile_error, compile_with},
+    };
+
+    #[test]
+    fn test_startup_and_termination() {
+        let source = "fn main() { d0.Setting = 1; }";
+        let program = compile(source);
+        assert_eq!(
+            program.lines,
+            [
+                "jal main",
+                "hcf",
+                "main:",
+                "move r1 1",
+                "s d0 Setting r1",
+                "j ra"
+            ]
+        );
+
+        let run = program.run(&[]);
+        assert_eq!(run.exit, Exit::Halted);
+        assert_eq!(run.writes, ["d0 Setting 1"]);
+        assert_eq!(run.steps, 5);
+
+        // restarting runs the top level and main again
+        let options = Options {
+            termination: Termination::Restart,
+            ..Options::default()
+        };
+        let program = compile_with(source, &options).unwrap();
+        assert_eq!(program.lines[1], "j 0");
+        let run = program.run(&[]);
+        assert_eq!(run.exit, Exit::Timeout);
+        assert_eq!(run.writes[..2], ["d0 Setting 1", "d0 Setting 1"]);
+
+        // the top level runs before main
+        let program = compile(
+            "def d1 as light; const ON = 1; fn main() { light.On = ON; d0.Setting = d0.Temperature; }",
+        );
+        assert_eq!(
+            program.writes(&[("d0 Temperature", 300.0)]),
+            ["d1 On 1", "d0 Setting 300"]
+        );
+
+        assert!(matches!(
+            compile_error("fn other() {}"),
+            Error::UndefinedMain
+        ));
+        assert!(matches!(
+            compile_error("fn main(a) {}"),
+            Error::WrongArgumentCount { .. }
+        ));
+    }
 }
@@ -0,0 +1,533 @@
+use stationeers_mips::{
+    instructions::{Arithmetic, FlowControl, Instruction, Misc, Stack},
+    types::{Line, Number, Register, RegisterOrNumber},
+};
+use std::collections::{HashMap, HashSet};
+
+/// A replacement for the first `length` instructions at some position.
+struct Rewrite {
+    length: usize,
+    /// the instruction taking the place of the first one, if any
+    replacement: Option<Instruction>,
+}
+
+/// A rule that looks at the instructions starting at a given line and may rewrite them.
+/// Rules only ever make code shorter, so applying them repeatedly terminates.
+type Rule = fn(&[Instruction], usize) -> Option<Rewrite>;
+
+const RULES: [Rule; 5] = [
+    redundant_push_pop,
+    merge_stack_adjustments,
+    add_zero_to_move,
+    redundant_move,
+    jump_to_next,
+];
+
+/// Rewrites wasteful instruction sequences left behind by the code generator.
+///
+/// The rules are applied until none of them matches anymore, and jumps to unconditional jumps
+/// are redirected to their final destination. Jumps to labels stay valid as code moves, jumps to
+/// line numbers are updated as instructions are removed.
+///
+/// Returns the new line of every original line, or `None` if the line was removed.
+pub(crate) fn optimize(instructions: &mut Vec<Instruction>) -> Vec<Option<usize>> {
+    let mut lines: Vec<Option<usize>> = (0..instructions.len()).map(Some).collect();
+
+    loop {
+        let threaded = thread_jumps(instructions);
+
+        let rewrites = find_rewrites(instructions);
+        if rewrites.is_empty() && !threaded {
+            return lines;
+        }
+
+        let moved = apply_rewrites(instructions, rewrites);
+        for line in lines.iter_mut() {
+            *line = line.and_then(|line| moved[line]);
+        }
+    }
+}
+
+/// Finds the rewrites that can be applied without changing the meaning of the program.
+/// A rewrite may not remove a line that is jumped to, or change the distance of a relative
+/// jump.
+fn find_rewrites(instructions: &[Instruction]) -> Vec<(usize, Rewrite)> {
+    let (targets, pinned) = analyze_jumps(instructions);
+
+    let mut rewrites = Vec::new();
+    let mut line = 0;
+    while line < instructions.len() {
+        let rewrite = RULES
+            .iter()
+            .filter_map(|rule| rule(instructions, line))
+            .find(|rewrite| {
+                let end = line + rewrite.length;
+                !(line..end).any(|line| pinned.contains(&line))
+                    && !(line + 1..end).any(|line| targets.contains(&line))
+            });
+
+        match rewrite {
+            Some(rewrite) => {
+                let length = rewrite.length;
+                rewrites.push((line, rewrite));
+                line += length;
+            }
+            None => line += 1,
+        }
+    }
+
+    rewrites
+}
+
+/// Collects the lines that execution can jump to without a label, and the lines that may not be
+/// moved because a relative jump depends on their position.
+fn analyze_jumps(instructions: &[Instruction]) -> (HashSet<usize>, HashSet<usize>) {
+    let mut targets = HashSet::new();
+    let mut pinned = HashSet::new();
+
+    for (line, instruction) in instructions.iter().enumerate() {
+        if let Some(target) = instruction.target() {
+            // calls return to the next line, and branches fall through to it
+            targets.insert(line + 1);
+            if let Some(target) = line_number(target) {
+                targets.insert(target);
+            }
+        }
+
+        match instruction.offset() {
+            Some(RegisterOrNumber::Number(Number::Int(offset))) => {
+                let target = (line as i64 + *offset as i64).max(0) as usize;
+                targets.insert(line + 1);
+                targets.insert(target);
+                pinned.extend(line.min(target)..=line.max(target));
+            }
+            Some(_) => {
+                // a computed relative jump goes into the jump table that follows it
+                pinned.insert(line);
+                let table = instructions[line + 1..]
+                    .iter()
+                    .take_while(|instruction| is_jump(instruction))
+                    .count();
+                for entry in line + 1..=line + table {
+                    pinned.insert(entry);
+                    targets.insert(entry);
+                }
+            }
+            None => {}
+        }
+    }
+
+    (targets, pinned)
+}
+
+/// Removes and replaces instructions, and updates jumps to line numbers to match.
+/// Returns the new line of every line, or `None` if the line was removed.
+fn apply_rewrites(
+    instructions: &mut Vec<Instruction>,
+    rewrites: Vec<(usize, Rewrite)>,
+) -> Vec<Option<usize>> {
+    let mut moved = Vec::with_capacity(instructions.len());
+    let mut optimized = Vec::with_capacity(instructions.len());
+
+    let mut rewrites = rewrites.into_iter().peekable();
+    let mut old = std::mem::take(instructions).into_iter();
+    while let Some(instruction) = old.next() {
+        let line = moved.len();
+        match rewrites.next_if(|(start, _)| *start == line) {
+            Some((_, rewrite)) => {
+                for _ in 1..rewrite.length {
+                    old.next();
+                }
+                match rewrite.replacement {
+                    Some(replacement) => {
+                        moved.push(Some(optimized.len()));
+                        optimized.push(replacement);
+                    }
+                    None => moved.push(None),
+                }
+                moved.extend(std::iter::repeat_n(None, rewrite.length - 1));
+            }
+            None => {
+                moved.push(Some(optimized.len()));
+                optimized.push(instruction);
+            }
+        }
+    }
+
+    // a jump to a removed line continues at the first line after it that is still there
+    let mut next = vec![optimized.len(); moved.len() + 1];
+    for line in (0..moved.len()).rev() {
+        next[line] = moved[line].unwrap_or(next[line + 1]);
+    }
+    for instruction in optimized.iter_mut() {
+        if let Some(target) = instruction.target_mut() {
+            if let Some(line) = line_number(target) {
+                *target = (next[line.min(moved.len())] as i32).into();
+            }
+        }
+    }
+
+    *instructions = optimized;
+    moved
+}
+
+/// Redirects jumps and branches that lead to an unconditional jump to where that jump goes.
+/// Returns whether any of them changed.
+fn thread_jumps(instructions: &mut [Instruction]) -> bool {
+    let labels: HashMap<String, usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(line, instruction)| match instruction {
+            Instruction::Misc(Misc::Label { name }) => Some((name.clone(), line)),
+            _ => None,
+        })
+        .collect();
+    // the label that each label leads to, if the code after it starts with a jump
+    let forward = |label: &str| match instructions.get(skip_labels(instructions, labels[label])) {
+        Some(Instruction::FlowControl(FlowControl::Jump {
+            a: Line::Label(next),
+        })) => Some(next.clone()),
+        _ => None,
+    };
+
+    let mut redirects = Vec::new();
+    for (line, instruction) in instructions.iter().enumerate() {
+        let Some(Line::Label(start)) = instruction.target() else {
+            continue;
+        };
+        if !labels.contains_key(start) {
+            continue;
+        }
+
+        // stop at the first label that was seen before, as the jumps may form a loop
+        let mut destination = start.clone();
+        let mut visited = HashSet::from([start.clone()]);
+        while let Some(next) = forward(&destination) {
+            if !labels.contains_key(&next) || !visited.insert(next.clone()) {
+                break;
+            }
+            destination = next;
+        }
+
+        if destination != *start {
+            redirects.push((line, destination));
+        }
+    }
+
+    let changed = !redirects.is_empty();
+    for (line, destination) in redirects {
+        if let Some(target) = instructions[line].target_mut() {
+            *target = Line::Label(destination);
+        }
+    }
+
+    changed
+}
+
+/// Finds the first line at or after `line` that is not a label or comment.
+fn skip_labels(instructions: &[Instruction], line: usize) -> usize {
+    line + instructions
+        .iter()
+        .skip(line)
+        .take_while(|instruction| {
+            matches!(
+                instruction,
+                Instruction::Misc(Misc::Label { .. } | Misc::Comment { .. })
+            )
+        })
+        .count()
+}
+
+/// `push a` followed by `pop r` is `move r a`, and nothing at all if `a` is `r`.
+fn redundant_push_pop(instructions: &[Instruction], line: usize) -> Option<Rewrite> {
+    let (
+        Some(Instruction::Stack(Stack::Push { a })),
+        Some(Instruction::Stack(Stack::Pop { register })),
+    ) = (instructions.get(line), instructions.get(line + 1))
+    else {
+        return None;
+    };
+
+    let replacement = (!is_register(a, *register)).then(|| {
+        Instruction::from(Misc::Move {
+            register: *register,
+            a: a.clone(),
+        })
+    });
+    Some(Rewrite {
+        length: 2,
+        replacement,
+    })
+}
+
+/// Consecutive constant changes to `sp` are combined into one, which is left out if they cancel
+/// each other out.
+fn merge_stack_adjustments(instructions: &[Instruction], line: usize) -> Option<Rewrite> {
+    let first = stack_adjustment(instructions.get(line)?)?;
+    if first == 0 {
+        return Some(Rewrite {
+            length: 1,
+            replacement: None,
+        });
+    }
+
+    let second = stack_adjustment(instructions.get(line + 1)?)?;
+    let total = first.checked_add(second)?;
+    let replacement = match total {
+        0 => None,
+        total if total > 0 => Some(Instruction::from(Arithmetic::Add {
+            register: Register::Sp,
+            a: Register::Sp.into(),
+            b: Number::Int(total).into(),
+        })),
+        total => Some(Instruction::from(Arithmetic::Subtract {
+            register: Register::Sp,
+            a: Register::Sp.into(),
+            b: Number::Int(-total).into(),
+        })),
+    };
+    Some(Rewrite {
+        length: 2,
+        replacement,
+    })
+}
+
+/// `add r 0 x`, `add r x 0` and `sub r x 0` are `move r x`.
+fn add_zero_to_move(instructions: &[Instruction], line: usize) -> Option<Rewrite> {
+    let (register, a) = match instructions.get(line)? {
+        Instruction::Arithmetic(Arithmetic::Add { register, a, b }) if is_zero(a) => (register, b),
+        Instruction::Arithmetic(
+            Arithmetic::Add { register, a, b } | Arithmetic::Subtract { register, a, b },
+        ) if is_zero(b) => (register, a),
+        _ => return None,
+    };
+
+    Some(Rewrite {
+        length: 1,
+        replacement: Some(Instruction::from(Misc::Move {
+            register: *register,
+            a: a.clone(),
+        })),
+    })
+}
+
+/// `move r r` does nothing.
+fn redundant_move(instructions: &[Instruction], line: usize) -> Option<Rewrite> {
+    match instructions.get(line)? {
+        Instruction::Misc(Misc::Move { register, a }) if is_register(a, *register) => {
+            Some(Rewrite {
+                length: 1,
+                replacement: None,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// A jump to a label right after it, with only labels and comments in between, does nothing.
+fn jump_to_next(instructions: &[Instruction], line: usize) -> Option<Rewrite> {
+    let Instruction::FlowControl(FlowControl::Jump {
+        a: Line::Label(label),
+    }) = instructions.get(line)?
+    else {
+        return None;
+    };
+
+    let end = skip_labels(instructions, line + 1);
+    instructions[line + 1..end]
+        .iter()
+        .any(|instruction| matches!(instruction, Instruction::Misc(Misc::Label { name }) if name == label))
+        .then_some(Rewrite {
+            length: 1,
+            replacement: None,
+        })
+}
+
+/// The amount a constant `add sp sp n` or `sub sp sp n` changes the stack pointer by.
+fn stack_adjustment(instruction: &Instruction) -> Option<i32> {
+    match instruction {
+        Instruction::Arithmetic(Arithmetic::Add {
+            register: Register::Sp,
+            a: RegisterOrNumber::Register(Register::Sp),
+            b: RegisterOrNumber::Number(Number::Int(n)),
+        }) => Some(*n),
+        Instruction::Arithmetic(Arithmetic::Subtract {
+            register: Register::Sp,
+            a: RegisterOrNumber::Register(Register::Sp),
+            b: RegisterOrNumber::Number(Number::Int(n)),
+        }) => n.checked_neg(),
+        _ => None,
+    }
+}
+
+/// The line number a jump goes to, if it is a constant.
+fn line_number(line: &Line) -> Option<usize> {
+    match line {
+        Line::Address(RegisterOrNumber::Number(Number::Int(line))) => usize::try_from(*line).ok(),
+        _ => None,
+    }
+}
+
+fn is_register(operand: &RegisterOrNumber, register: Register) -> bool {
+    matches!(operand, RegisterOrNumber::Register(r) if *r == register)
+}
+
+fn is_zero(operand: &RegisterOrNumber) -> bool {
+    match operand {
+        RegisterOrNumber::Number(Number::Int(i)) => *i == 0,
+        RegisterOrNumber::Number(Number::Float(f)) => *f == 0.0,
+        RegisterOrNumber::Register(_) => false,
+    }
+}
+
+fn is_jump(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::FlowControl(FlowControl::Jump { .. })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(instructions: &[Instruction]) -> Vec<String> {
+        instructions.iter().map(ToString::to_string).collect()
+    }
+
+    fn push(a: RegisterOrNumber) -> Instruction {
+        Stack::Push { a }.into()
+    }
+
+    fn pop(register: Register) -> Instruction {
+        Stack::Pop { register }.into()
+    }
+
+    fn adjust_sp(n: i32) -> Instruction {
+        Arithmetic::Add {
+            register: Register::Sp,
+            a: Register::Sp.into(),
+            b: Number::Int(n).into(),
+        }
+        .into()
+    }
+
+    fn jump(label: &str) -> Instruction {
+        FlowControl::Jump {
+            a: Line::Label(label.to_string()),
+        }
+        .into()
+    }
+
+    fn label(name: &str) -> Instruction {
+        Misc::Label {
+            name: name.to_string(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_redundant_push_pop() {
+        let same = [push(Register::R4.into()), pop(Register::R4)];
+        let rewrite = redundant_push_pop(&same, 0).unwrap();
+        assert_eq!(rewrite.length, 2);
+        assert!(rewrite.replacement.is_none());
+
+        let different = [push(Number::Int(3).into()), pop(Register::R0)];
+        let rewrite = redundant_push_pop(&different, 0).unwrap();
+        assert_eq!(rewrite.replacement.unwrap().to_string(), "move r0 3");
+
+        let reversed = [pop(Register::R0), push(Register::R0.into())];
+        assert!(redundant_push_pop(&reversed, 0).is_none());
+    }
+
+    #[test]
+    fn test_merge_stack_adjustments() {
+        let cancelled = [adjust_sp(-1), adjust_sp(1)];
+        let rewrite = merge_stack_adjustments(&cancelled, 0).unwrap();
+        assert_eq!(rewrite.length, 2);
+        assert!(rewrite.replacement.is_none());
+
+        let merged = [adjust_sp(-3), adjust_sp(1)];
+        let rewrite = merge_stack_adjustments(&merged, 0).unwrap();
+        assert_eq!(rewrite.replacement.unwrap().to_string(), "sub sp sp 2");
+
+        let empty = [adjust_sp(0)];
+        assert_eq!(merge_stack_adjustments(&empty, 0).unwrap().length, 1);
+    }
+
+    #[test]
+    fn test_add_zero_to_move() {
+        let add = [Instruction::from(Arithmetic::Add {
+            register: Register::R4,
+            a: Number::Int(0).into(),
+            b: Register::R5.into(),
+        })];
+        let rewrite = add_zero_to_move(&add, 0).unwrap();
+        assert_eq!(rewrite.replacement.unwrap().to_string(), "move r4 r5");
+
+        let no_op = [Instruction::from(Arithmetic::Add {
+            register: Register::R0,
+            a: Register::R0.into(),
+            b: Number::Int(0).into(),
+        })];
+        let rewrite = add_zero_to_move(&no_op, 0).unwrap();
+        let replacement = [rewrite.replacement.unwrap()];
+        assert!(redundant_move(&replacement, 0).is_some());
+    }
+
+    #[test]
+    fn test_jump_to_next() {
+        let instructions = [jump("end"), label("end"), jump("end")];
+        assert!(jump_to_next(&instructions, 0).is_some());
+        assert!(jump_to_next(&instructions, 2).is_none());
+    }
+
+    #[test]
+    fn test_thread_jumps() {
+        let mut instructions = vec![
+            jump("a"),
+            Misc::Yield.into(),
+            label("a"),
+            jump("b"),
+            label("b"),
+        ];
+        assert!(thread_jumps(&mut instructions));
+        assert_eq!(render(&instructions)[0], "j b");
+
+        // jumps that form a loop are left alone
+        let mut instructions = vec![jump("a"), label("a"), jump("a")];
+        assert!(!thread_jumps(&mut instructions));
+    }
+
+    #[test]
+    fn test_optimize_remaps_lines() {
+        let mut instructions = vec![
+            push(Register::R4.into()),
+            pop(Register::R4),
+            label("loop"),
+            Misc::Yield.into(),
+            FlowControl::Jump { a: 3.into() }.into(),
+        ];
+        let lines = optimize(&mut instructions);
+
+        assert_eq!(render(&instructions), ["loop:", "yield", "j 1"]);
+        assert_eq!(lines, [None, None, Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_relative_jumps_are_kept() {
+        let mut instructions = vec![
+            FlowControl::JumpRelative {
+                a: Register::R0.into(),
+            }
+            .into(),
+            jump("end"),
+            jump("end"),
+            label("end"),
+        ];
+        optimize(&mut instructions);
+
+        assert_eq!(render(&instructions), ["jr r0", "j end", "j end", "end:"]);
+    }
+}
@@ -0,0 +1,199 @@
+use ayysee_ir::{cfg::Cfg, Function, Op, Program, Terminator};
+use stationeers_mips::types::Device;
+use std::collections::HashSet;
+
+/// Removes the parts of a program that can never run, warning about each of them.
+///
+/// Everything is traced from the top-level initialisation, which is where execution starts and
+/// which calls `main`:
+/// - Branches on conditions that are known at compile time become jumps.
+/// - Functions that are never called from code that runs are removed.
+/// - Blocks that cannot be reached from the start of their function are removed.
+/// - Aliases of devices that none of the remaining code accesses are removed.
+pub(crate) fn eliminate_dead_code(program: &mut Program) {
+    for function in functions_mut(program) {
+        for block in &mut function.blocks {
+            block.terminator.fold_constant();
+        }
+    }
+
+    remove_uncalled_functions(program);
+
+    remove_unreachable_blocks(&mut program.init, "the top level");
+    for function in &mut program.functions {
+        let location = format!("function {}", function.name);
+        remove_unreachable_blocks(function, &location);
+    }
+
+    remove_unused_aliases(program);
+}
+
+/// Removes the functions that are never called, starting from the top level.
+/// Calls in blocks that cannot be reached do not count.
+fn remove_uncalled_functions(program: &mut Program) {
+    let mut called: HashSet<String> = HashSet::new();
+    let mut pending = vec![&program.init];
+    while let Some(function) = pending.pop() {
+        let reachable = Cfg::new(function).reachable();
+        let calls = function
+            .blocks
+            .iter()
+            .zip(reachable)
+            .filter(|(_, reachable)| *reachable)
+            .flat_map(|(block, _)| &block.ops)
+            .filter_map(|op| match op {
+                Op::Call { function, .. } => Some(function),
+                _ => None,
+            });
+
+        for name in calls {
+            if called.insert(name.clone()) {
+                pending.extend(program.function(name));
+            }
+        }
+    }
+
+    program.functions.retain(|function| {
+        let is_called = called.contains(&function.name);
+        if !is_called {
+            tracing::warn!("function {} is never called and was removed", function.name);
+        }
+        is_called
+    });
+}
+
+/// Removes the blocks of a function that cannot be reached from its entry.
+/// Blocks that only hold the implicit end of a statement, such as the code after an `if` whose
+/// branches both return, are removed without a warning.
+fn remove_unreachable_blocks(function: &mut Function, location: &str) {
+    let reachable = Cfg::new(function).reachable();
+    if reachable.iter().all(|reachable| *reachable) {
+        return;
+    }
+
+    let removes_code = function
+        .blocks
+        .iter()
+        .zip(&reachable)
+        .any(|(block, reachable)| !reachable && !block.ops.is_empty());
+    if removes_code {
+        tracing::warn!("unreachable code in {location} was removed");
+    }
+
+    function.retain_blocks(&reachable);
+}
+
+/// Removes the aliases of devices that are never accessed.
+fn remove_unused_aliases(program: &mut Program) {
+    let mut accessed: HashSet<Device> = HashSet::new();
+    let functions = std::iter::once(&program.init).chain(&program.functions);
+    for block in functions.flat_map(|function| &function.blocks) {
+        accessed.extend(block.ops.iter().filter_map(Op::device));
+        if let Terminator::Branch { condition, .. } = &block.terminator {
+            accessed.extend(condition.device());
+        }
+    }
+
+    for block in functions_mut(program).flat_map(|function| &mut function.blocks) {
+        block.ops.retain(|op| match op {
+            Op::Alias { name, device, .. } if !accessed.contains(device) => {
+                tracing::warn!("alias {name} is never used and was removed");
+                false
+            }
+            _ => true,
+        });
+    }
+}
+
+/// The top-level initialisation followed by every function.
+fn functions_mut(program: &mut Program) -> impl Iterator<Item = &mut Function> {
+    std::iter::once(&mut program.init).chain(&mut program.functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ayysee_ir::{BasicBlock, BlockId, Comparison, Condition};
+    use stationeers_mips::types::{DeviceVariable, Number};
+
+    #[test]
+    fn test_eliminate_dead_code() {
+        let mut init = Function::new("init".to_string());
+        let mut entry = BasicBlock::new("init".to_string());
+        entry.ops.push(Op::Alias {
+            name: "sensor".to_string(),
+            device: Device::D0,
+            docs: Vec::new(),
+        });
+        entry.ops.push(Op::Alias {
+            name: "unused".to_string(),
+            device: Device::D1,
+            docs: Vec::new(),
+        });
+        entry.ops.push(Op::Call {
+            dest: None,
+            function: "main".to_string(),
+            arguments: Vec::new(),
+        });
+        entry.terminator = Terminator::Halt;
+        init.blocks.push(entry);
+
+        // main only calls `debug` in a branch that is never taken
+        let mut main = Function::new("main".to_string());
+        let mut entry = BasicBlock::new("main".to_string());
+        entry.terminator = Terminator::Branch {
+            condition: Condition::Compare {
+                comparison: Comparison::NotEqual,
+                a: Number::Int(0).into(),
+                b: Number::Int(0).into(),
+            },
+            then: BlockId(1),
+            otherwise: BlockId(2),
+        };
+        main.blocks.push(entry);
+        let mut debug = BasicBlock::new("if_0".to_string());
+        debug.ops.push(Op::Call {
+            dest: None,
+            function: "debug".to_string(),
+            arguments: Vec::new(),
+        });
+        debug.terminator = Terminator::Jump(BlockId(2));
+        main.blocks.push(debug);
+        let mut end = BasicBlock::new("if_0_end".to_string());
+        let value = main.new_register();
+        end.ops.push(Op::LoadDevice {
+            dest: value,
+            device: Device::D0,
+            variable: DeviceVariable::Temperature,
+        });
+        main.blocks.push(end);
+
+        let mut debug = Function::new("debug".to_string());
+        debug.blocks.push(BasicBlock::new("debug".to_string()));
+
+        let mut program = Program {
+            init,
+            functions: vec![main, debug],
+        };
+        eliminate_dead_code(&mut program);
+
+        assert_eq!(program.functions.len(), 1);
+        let main = program.function("main").unwrap();
+        assert_eq!(main.blocks.len(), 2);
+        assert!(matches!(
+            main.blocks[0].terminator,
+            Terminator::Jump(BlockId(1))
+        ));
+        assert_eq!(main.blocks[1].name, "if_0_end");
+
+        let aliases: Vec<&String> = program.init.blocks[0]
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Alias { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(aliases, ["sensor"]);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::Location;
+use ayysee_ir::{liveness::Liveness, Function, VReg};
+use stationeers_mips::types::Register;
+use std::collections::HashMap;
+
+/// Registers that hold virtual registers.
+/// `r0`-`r3` are left out because they pass arguments and return values, and are used as scratch
+/// registers for values that are only needed by the next instruction.
+pub(crate) const ALLOCATABLE_REGISTERS: [Register; 12] = [
+    Register::R4,
+    Register::R5,
+    Register::R6,
+    Register::R7,
+    Register::R8,
+    Register::R9,
+    Register::R10,
+    Register::R11,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+/// Where the virtual registers of a function are kept.
+pub(crate) struct Allocation {
+    locations: HashMap<VReg, Location>,
+    /// The number of stack slots needed for the values that did not fit in registers
+    pub(crate) slots: i32,
+}
+
+impl Allocation {
+    /// The location of a virtual register.
+    /// Registers whose value is never read have no location.
+    pub(crate) fn location(&self, register: VReg) -> Option<Location> {
+        self.locations.get(&register).copied()
+    }
+}
+
+/// The range of positions over which a virtual register holds a value.
+#[derive(Copy, Clone, Debug)]
+struct Interval {
+    register: VReg,
+    start: usize,
+    end: usize,
+    /// The number of operations that read or write the register
+    uses: usize,
+}
+
+impl Interval {
+    /// How much keeping the register on the stack would cost for every position it covers.
+    /// Values that are used often over a short range are the most worth keeping in registers.
+    fn spill_weight(&self) -> f64 {
+        self.uses as f64 / (self.end - self.start + 1) as f64
+    }
+}
+
+/// Maps the virtual registers of a function onto machine registers with linear scan allocation.
+///
+/// Each virtual register gets a single interval that covers everywhere it is live, so registers
+/// that live across a loop keep their location for the whole loop. When more values are live
+/// than there are registers, the ones that are used the least for how long they live are spilled
+/// to stack slots.
+pub(crate) fn allocate(function: &Function, liveness: &Liveness) -> Allocation {
+    let mut intervals = live_intervals(function, liveness);
+    intervals.sort_by_key(|interval| (interval.start, interval.register));
+
+    let mut locations = HashMap::new();
+    let mut spilled = Vec::new();
+    let mut active: Vec<(Interval, Register)> = Vec::new();
+    let mut free: Vec<Register> = ALLOCATABLE_REGISTERS.iter().rev().copied().collect();
+
+    for interval in intervals {
+        // free the registers of intervals that have ended
+        active.retain(|(other, register)| {
+            let ended = other.end < interval.start;
+            if ended {
+                free.push(*register);
+            }
+            !ended
+        });
+        free.sort_by_key(|register| std::cmp::Reverse(*register as u8));
+
+        if let Some(register) = free.pop() {
+            locations.insert(interval.register, Location::Register(register));
+            active.push((interval, register));
+            continue;
+        }
+
+        // spill whichever value is the cheapest to keep on the stack
+        let (cheapest, _) = active
+            .iter()
+            .enumerate()
+            .min_by(|(_, (a, _)), (_, (b, _))| a.spill_weight().total_cmp(&b.spill_weight()))
+            .expect("all registers are taken by active intervals");
+        if active[cheapest].0.spill_weight() < interval.spill_weight() {
+            let (other, register) = active.swap_remove(cheapest);
+            spilled.push(other);
+            locations.insert(interval.register, Location::Register(register));
+            active.push((interval, register));
+        } else {
+            spilled.push(interval);
+        }
+    }
+
+    // spilled values share stack slots in the same way
+    spilled.sort_by_key(|interval| (interval.start, interval.register));
+    let mut slots = 0;
+    let mut free_slots: Vec<i32> = Vec::new();
+    let mut active_slots: Vec<(Interval, i32)> = Vec::new();
+    for interval in spilled {
+        active_slots.retain(|(other, slot)| {
+            let ended = other.end < interval.start;
+            if ended {
+                free_slots.push(*slot);
+            }
+            !ended
+        });
+        free_slots.sort_by_key(|slot| std::cmp::Reverse(*slot));
+
+        let slot = free_slots.pop().unwrap_or_else(|| {
+            slots += 1;
+            slots - 1
+        });
+        locations.insert(interval.register, Location::Stack(slot));
+        active_slots.push((interval, slot));
+    }
+
+    Allocation { locations, slots }
+}
+
+/// Works out the interval of every virtual register.
+///
+/// Blocks are numbered in layout order. Every operation has two positions, one where it reads its
+/// operands and one where it writes its result, so that a register can be reused by the
+/// operation that reads its last value.
+fn live_intervals(function: &Function, liveness: &Liveness) -> Vec<Interval> {
+    let mut ranges: HashMap<VReg, (usize, usize)> = HashMap::new();
+    let mut uses: HashMap<VReg, usize> = HashMap::new();
+    let mut extend = |register: VReg, position: usize| {
+        let range = ranges.entry(register).or_insert((position, position));
+        range.0 = range.0.min(position);
+        range.1 = range.1.max(position);
+    };
+
+    let mut position = 0;
+    for id in function.block_ids() {
+        let block = function.block(id);
+        let start = position;
+        let end = start + 2 * block.ops.len() + 2;
+
+        for register in liveness.live_in(id) {
+            extend(*register, start);
+        }
+        for register in liveness.live_out(id) {
+            extend(*register, end);
+        }
+        for register in block.terminator.uses() {
+            extend(register, end - 1);
+            *uses.entry(register).or_default() += 1;
+        }
+
+        let live_after = liveness.live_after_ops(function, id);
+        for (i, op) in block.ops.iter().enumerate() {
+            let read = start + 1 + 2 * i;
+            let write = read + 1;
+
+            // values that are live across the operation must not share a register with its
+            // result
+            for register in &live_after[i] {
+                extend(*register, write);
+            }
+            if let Some(dest) = op.dest() {
+                extend(dest, write);
+                *uses.entry(dest).or_default() += 1;
+            }
+            for register in op.uses() {
+                extend(register, read);
+                *uses.entry(register).or_default() += 1;
+            }
+        }
+
+        position = end + 1;
+    }
+
+    ranges
+        .into_iter()
+        .map(|(register, (start, end))| Interval {
+            register,
+            start,
+            end,
+            uses: uses.get(&register).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emulator::Exit, testing::compile};
+    use ayysee_ir::{cfg::Cfg, BasicBlock, BinaryOp, Op, Operand, Terminator};
+    use stationeers_mips::types::Number;
+
+    #[test]
+    fn test_values_spill_to_stack() {
+        // more values are live at once than there are registers
+        let mut function = Function::new("main".to_string());
+        function.blocks.push(BasicBlock::new("main".to_string()));
+        let values: Vec<VReg> = (0..14).map(|_| function.new_register()).collect();
+        for (i, value) in values.iter().enumerate() {
+            function.blocks[0].ops.push(Op::Move {
+                dest: *value,
+                source: Number::Int(i as i32).into(),
+            });
+        }
+        let mut sum: Operand = Number::Int(0).into();
+        for value in &values {
+            let register = function.new_register();
+            function.blocks[0].ops.push(Op::Binary {
+                dest: register,
+                op: BinaryOp::Add,
+                a: sum,
+                b: (*value).into(),
+            });
+            sum = register.into();
+        }
+        function.blocks[0].terminator = Terminator::Return(Some(sum));
+
+        let cfg = Cfg::new(&function);
+        let liveness = Liveness::new(&function, &cfg);
+        let allocation = allocate(&function, &liveness);
+
+        assert!(matches!(
+            allocation.location(values[0]),
+            Some(Location::Register(Register::R4))
+        ));
+        let spilled = values
+            .iter()
+            .filter(|value| matches!(allocation.location(**value), Some(Location::Stack(_))))
+            .count();
+        assert_eq!(spilled, values.len() - ALLOCATABLE_REGISTERS.len());
+        assert_eq!(allocation.slots, spilled as i32);
+
+        // the first sum reuses the register of the value it has just read
+        assert!(matches!(
+            allocation.location(VReg(values.len() as u32)),
+            Some(Location::Register(Register::R4))
+        ));
+    }
+
+    #[test]
+    fn test_spilling_in_a_program() {
+        // fifteen values are live at once, which is more than there are registers for, and the
+        // fifth argument of `weigh` is passed on the stack
+        let program = compile(
+            "fn weigh(a, b, c, d, e) { return a + 2 * b + 3 * c + 4 * d + 5 * e; }
+            fn main() {
+                let v0 = d0.Setting; let v1 = d0.Setting + 1; let v2 = d0.Setting + 2;
+                let v3 = d0.Setting + 3; let v4 = d0.Setting + 4; let v5 = d0.Setting + 5;
+                let v6 = d0.Setting + 6; let v7 = d0.Setting + 7; let v8 = d0.Setting + 8;
+                let v9 = d0.Setting + 9; let v10 = d0.Setting + 10; let v11 = d0.Setting + 11;
+                let v12 = d0.Setting + 12; let v13 = d0.Setting + 13; let v14 = d0.Setting + 14;
+                let w = weigh(v0, v1, v2, v3, v14);
+                d1.Setting = v0 + v1 + v2 + v3 + v4 + v5 + v6 + v7 + v8 + v9 + v10 + v11 + v12
+                    + v13 + v14;
+                d1.On = w + v13;
+            }",
+        );
+        assert!(program
+            .lines
+            .iter()
+            .any(|line| line.starts_with("add sp sp")));
+
+        let run = program.run(&[("d0 Setting", 10.0)]);
+        assert_eq!(run.exit, Exit::Halted);
+        // 15 * 10 + (0 + 1 + ... + 14), and 10 + 2 * 11 + 3 * 12 + 4 * 13 + 5 * 24 + 23
+        assert_eq!(run.writes, ["d1 Setting 255", "d1 On 263"]);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::error::{Error, Result};
+use stationeers_mips::{
+    instructions::{Instruction, Misc},
+    types::Line,
+};
+use std::collections::HashMap;
+
+/// Finds the line that each label resolves to, which is the line after it, so that jumping to it
+/// does not spend an instruction on the label itself.
+/// A label that is defined twice is an error, as jumps to it would be ambiguous. This holds
+/// whether the labels are relocated or left for the game to resolve.
+pub(crate) fn label_lines(instructions: &[Instruction]) -> Result<HashMap<String, i32>> {
+    let mut labels: HashMap<String, i32> = HashMap::new();
+    for (line, instruction) in instructions.iter().enumerate() {
+        if let Instruction::Misc(Misc::Label { name }) = instruction {
+            if labels.insert(name.clone(), line as i32 + 1).is_some() {
+                return Err(Error::DuplicateLabel(name.clone()));
+            }
+        }
+    }
+
+    Ok(labels)
+}
+
+/// Resolves the labels that jumps and branches refer to into line numbers.
+/// The label lines are kept as they are.
+pub(crate) fn relocate(instructions: &mut [Instruction]) -> Result<()> {
+    let labels = label_lines(instructions)?;
+
+    for instruction in instructions.iter_mut() {
+        if let Some(target) = instruction.target_mut() {
+            if let Line::Label(label) = target {
+                let line = *labels
+                    .get(label)
+                    .ok_or_else(|| Error::UndefinedLabel(label.clone()))?;
+                *target = line.into();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stationeers_mips::instructions::FlowControl;
+
+    #[test]
+    fn test_relocate() {
+        let mut instructions: Vec<Instruction> = vec![
+            FlowControl::Jump {
+                a: Line::Label("end".to_string()),
+            }
+            .into(),
+            Misc::Yield.into(),
+            Misc::Label {
+                name: "end".to_string(),
+            }
+            .into(),
+        ];
+        relocate(&mut instructions).unwrap();
+        assert_eq!(instructions[0].to_string(), "j 3");
+
+        let mut undefined: Vec<Instruction> = vec![FlowControl::Jump {
+            a: Line::Label("missing".to_string()),
+        }
+        .into()];
+        assert!(matches!(
+            relocate(&mut undefined),
+            Err(Error::UndefinedLabel(_))
+        ));
+
+        let label = || -> Instruction {
+            Misc::Label {
+                name: "end".to_string(),
+            }
+            .into()
+        };
+        let mut duplicate = vec![label(), Misc::Yield.into(), label()];
+        assert!(matches!(
+            label_lines(&duplicate),
+            Err(Error::DuplicateLabel(label)) if label == "end"
+        ));
+        assert!(matches!(
+            relocate(&mut duplicate),
+            Err(Error::DuplicateLabel(label)) if label == "end"
+        ));
+    }
+}
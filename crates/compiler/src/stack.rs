@@ -1,99 +1,217 @@
 use crate::{
     codegen::CodeGenerator,
+    regalloc::Allocation,
     util::{stack_pop, stack_push},
     Location,
 };
-use stationeers_mips::{instructions::Stack as StackInstruction, types::Register};
-use std::collections::HashMap;
-
-/// Utility struct for managing the stack.
-pub(crate) struct Stack {
-    rsp_offset: i32,
-
-    pub(crate) locals: HashMap<String, Location>,
-    saved_registers: Vec<Register>,
-    /// Keeps track of the loops that are currently active.
-    loops: Vec<String>,
+use ayysee_ir::{Operand, VReg};
+use stationeers_mips::{
+    instructions::{Arithmetic, Instruction, Misc, Stack as StackInstruction},
+    types::{Number, Register, RegisterOrNumber},
+};
 
-    loop_counter: i32,
-    if_counter: i32,
+/// The stack frame of the function that code is being emitted for.
+///
+/// The frame starts where the stack pointer was when the function was entered. It holds the stack
+/// slots of values that did not fit in registers, followed by everything that is pushed while the
+/// function runs, such as the registers that are saved around calls. Arguments after the fourth
+/// are pushed by the caller right below the frame.
+pub(crate) struct Frame {
+    /// The number of values between the base of the frame and the top of the stack.
+    /// Every push and pop must be tracked so that slots can be found relative to the top of the
+    /// stack.
+    pub(crate) rsp_offset: i32,
+    allocation: Allocation,
 }
 
-impl Stack {
-    pub(crate) fn new() -> Self {
+impl Frame {
+    pub(crate) fn new(allocation: Allocation) -> Self {
         Self {
             rsp_offset: 0,
-            locals: HashMap::new(),
-            saved_registers: Vec::new(),
-            loops: Vec::new(),
-            loop_counter: 0,
-            if_counter: 0,
+            allocation,
+        }
+    }
+
+    /// Where a virtual register is kept.
+    pub(crate) fn location(&self, register: VReg) -> Option<Location> {
+        self.allocation.location(register)
+    }
+
+    /// Makes room for the stack slots of the function.
+    pub(crate) fn enter(&mut self, codegen: &mut CodeGenerator) {
+        let slots = self.allocation.slots;
+        if slots > 0 {
+            codegen.add_instruction(Instruction::from(Arithmetic::Add {
+                register: Register::Sp,
+                a: Register::Sp.into(),
+                b: Number::Int(slots).into(),
+            }));
+            codegen.add_comment(format!("allocate {slots} stack slots"));
+            self.rsp_offset += slots;
         }
     }
 
-    /// Allocates space on the stack for a local variable.
-    /// The variable will be initialized to 0.
-    pub(crate) fn allocate_local(&mut self, name: String) {
-        self.rsp_offset += 1;
-        self.locals.insert(name, Location::Stack(self.rsp_offset));
+    /// Removes the frame from the stack before the function is left.
+    /// The frame is still tracked, as code after a return can be reached from elsewhere.
+    pub(crate) fn leave(&self, codegen: &mut CodeGenerator) {
+        if self.rsp_offset != 0 {
+            codegen.add_instruction(Instruction::from(Arithmetic::Subtract {
+                register: Register::Sp,
+                a: Register::Sp.into(),
+                b: Number::Int(self.rsp_offset).into(),
+            }));
+        }
     }
 
-    /// Makes the stack aware of a local variable that has already been allocated.
-    /// This will not allocate any space on the stack but will allow the stack to
-    /// reference the variable.
-    pub(crate) fn allocate_local_at(&mut self, name: String, location: Location) {
-        match location {
-            Location::Stack(offset) => self
-                .locals
-                .insert(name, Location::Stack(self.rsp_offset + offset)),
-            Location::Register(register) => self.locals.insert(name, Location::Register(register)),
-        };
+    /// Pushes a value onto the stack.
+    pub(crate) fn push(&mut self, value: RegisterOrNumber, codegen: &mut CodeGenerator) {
+        stack_push!(codegen, self, value);
     }
 
-    /// Deallocates a local variable.
-    pub(crate) fn deallocate_local(&mut self, name: String) {
-        self.rsp_offset -= 1;
-        self.locals.remove(&name);
+    /// Pops the value on top of the stack into a register.
+    pub(crate) fn pop(&mut self, register: Register, codegen: &mut CodeGenerator) {
+        stack_pop!(codegen, self, register);
     }
 
-    /// Allocates space on the stack for a saved register.
-    pub(crate) fn save_register(&mut self, register: Register, codegen: &mut CodeGenerator) {
-        self.rsp_offset += 1;
-        self.saved_registers.push(register);
-        stack_push!(codegen, register);
+    /// Removes values from the top of the stack without reading them.
+    pub(crate) fn discard(&mut self, count: i32, codegen: &mut CodeGenerator) {
+        if count > 0 {
+            codegen.add_instruction(Instruction::from(Arithmetic::Subtract {
+                register: Register::Sp,
+                a: Register::Sp.into(),
+                b: Number::Int(count).into(),
+            }));
+            self.rsp_offset -= count;
+        }
     }
 
-    /// Deallocates a saved register and restores its value.
-    pub(crate) fn restore_register(&mut self, register: Register, codegen: &mut CodeGenerator) {
-        self.rsp_offset -= 1;
-        self.saved_registers.pop();
-        stack_pop!(codegen, register);
+    /// Reads a stack slot into a register.
+    /// Slots are counted from the base of the frame, so arguments on the stack have negative
+    /// slots.
+    pub(crate) fn load_slot(&self, slot: i32, register: Register, codegen: &mut CodeGenerator) {
+        // `peek` reads the value right below the stack pointer
+        let offset = self.rsp_offset - slot - 1;
+        if offset != 0 {
+            codegen.add_instruction(Instruction::from(Arithmetic::Subtract {
+                register: Register::Sp,
+                a: Register::Sp.into(),
+                b: Number::Int(offset).into(),
+            }));
+        }
+        codegen.add_instruction(Instruction::from(StackInstruction::Peek { register }));
+        codegen.add_comment(format!("load stack slot {slot}"));
+        if offset != 0 {
+            codegen.add_instruction(Instruction::from(Arithmetic::Add {
+                register: Register::Sp,
+                a: Register::Sp.into(),
+                b: Number::Int(offset).into(),
+            }));
+        }
     }
 
-    /// Marks the beginning of a loop.
-    pub(crate) fn new_loop(&mut self) -> String {
-        let name = format!("loop_{}", self.loop_counter);
-        self.loop_counter += 1;
-        self.loops.push(name.clone());
+    /// Writes a value into a stack slot.
+    pub(crate) fn store_slot(
+        &self,
+        slot: i32,
+        value: RegisterOrNumber,
+        codegen: &mut CodeGenerator,
+    ) {
+        // move the stack pointer to the slot, and overwrite it with a push
+        let offset = self.rsp_offset - slot - 1;
+        codegen.add_instruction(Instruction::from(Arithmetic::Subtract {
+            register: Register::Sp,
+            a: Register::Sp.into(),
+            b: Number::Int(offset + 1).into(),
+        }));
+        codegen.add_instruction(StackInstruction::Push { a: value }.into());
+        codegen.add_comment(format!("store stack slot {slot}"));
+        if offset != 0 {
+            codegen.add_instruction(Instruction::from(Arithmetic::Add {
+                register: Register::Sp,
+                a: Register::Sp.into(),
+                b: Number::Int(offset).into(),
+            }));
+        }
+    }
 
-        name
+    /// Gets an operand ready to be used by an instruction.
+    /// Values on the stack are loaded into `scratch`.
+    pub(crate) fn operand(
+        &self,
+        operand: &Operand,
+        scratch: Register,
+        codegen: &mut CodeGenerator,
+    ) -> RegisterOrNumber {
+        match operand {
+            Operand::Constant(number) => number.clone().into(),
+            Operand::Register(register) => match self.location(*register) {
+                Some(Location::Register(register)) => register.into(),
+                Some(Location::Stack(slot)) => {
+                    self.load_slot(slot, scratch, codegen);
+                    scratch.into()
+                }
+                // the value is never written, so any register will do
+                None => scratch.into(),
+            },
+        }
     }
 
-    pub(crate) fn new_if(&mut self) -> String {
-        let name = format!("if_{}", self.if_counter);
-        self.if_counter += 1;
+    /// Gets an operand into a register for instructions that only accept registers.
+    /// Numbers and values on the stack are placed in `scratch`.
+    pub(crate) fn register_operand(
+        &self,
+        operand: &Operand,
+        scratch: Register,
+        codegen: &mut CodeGenerator,
+    ) -> Register {
+        match self.operand(operand, scratch, codegen) {
+            RegisterOrNumber::Register(register) => register,
+            number => {
+                codegen.add_instruction(Instruction::from(Misc::Move {
+                    register: scratch,
+                    a: number,
+                }));
+                scratch
+            }
+        }
+    }
 
-        name
+    /// The register that an instruction writes the value of a virtual register to.
+    /// Values that live on the stack are written to `r0` and stored with `finish_write`.
+    pub(crate) fn write_register(&self, register: VReg) -> Register {
+        match self.location(register) {
+            Some(Location::Register(register)) => register,
+            _ => Register::R0,
+        }
     }
 
-    /// Marks the end of a loop.
-    pub(crate) fn end_loop(&mut self) -> Option<String> {
-        self.loops.pop()
+    /// Stores a value that was written to `r0` by `write_register` in its stack slot.
+    pub(crate) fn finish_write(&self, register: VReg, codegen: &mut CodeGenerator) {
+        if let Some(Location::Stack(slot)) = self.location(register) {
+            self.store_slot(slot, Register::R0.into(), codegen);
+        }
     }
 
-    /// Clears values between passes.
-    pub(crate) fn clear(&mut self) {
-        self.loop_counter = 0;
-        self.if_counter = 0;
+    /// Copies a value into the location of a virtual register.
+    pub(crate) fn assign(
+        &self,
+        register: VReg,
+        value: RegisterOrNumber,
+        codegen: &mut CodeGenerator,
+    ) {
+        match self.location(register) {
+            // a value that is already in place is not moved
+            Some(Location::Register(target)) if matches!(value, RegisterOrNumber::Register(source) if source == target) =>
+                {}
+            Some(Location::Register(target)) => {
+                codegen.add_instruction(Instruction::from(Misc::Move {
+                    register: target,
+                    a: value,
+                }));
+            }
+            Some(Location::Stack(slot)) => self.store_slot(slot, value, codegen),
+            // the value is never read
+            None => {}
+        }
     }
 }
@@ -1,275 +1,301 @@
 use crate::{
-    codegen::CodeGenerator,
+    builder::Builder,
+    codegen::{CodeGenerator, FunctionSignature},
+    constant::evaluate_constant,
     error::{Error, Result},
-    expr::generate_expr,
-    stack::Stack,
-    util::{assign_variable, function_return, stack_pop, stack_push},
-    Location, Pass,
+    expr::{constant_operand, lower_condition, lower_expr, lower_expr_into, resolve_type_hash},
+    intrinsics::{lower_intrinsic, Intrinsic},
 };
-use ayysee_parser::ast::{Block, Expr, Identifier, IfStatement, Statement};
-use stationeers_mips::{
-    instructions::{
-        Arithmetic, DeviceIo, FlowControl, Instruction, Misc, Stack as StackInstruction,
-    },
-    types::{Device, DeviceVariable, Number, Register},
+use ayysee_ir::{cfg::Cfg, Comparison, Condition, Function, Op, Operand, Terminator, VReg};
+use ayysee_parser::ast::{
+    Block, Expr, Identifier, IfStatement, MatchArm, MatchPattern, Statement, Value,
 };
+use stationeers_mips::types::{Device, DeviceVariable, Number, SlotVariable};
 use std::str::FromStr;
 
-/// Evaluates a single statement and generates the corresponding MIPS assembly code.
-pub(crate) fn generate_statement(
+/// Lowers a single statement into the current function.
+pub(crate) fn lower_statement(
     statement: &Statement,
-    stack: &mut Stack,
+    builder: &mut Builder,
     codegen: &mut CodeGenerator,
-    pass: Pass,
 ) -> Result<()> {
     match statement {
         Statement::Assignment {
             identifier,
             expression,
         } => {
-            codegen.add_comment_line(format!("Assignment: {identifier:?} {expression:?}"));
+            builder.push(Op::Comment(format!(
+                "Assignment: {identifier:?} {expression:?}"
+            )));
 
-            let identifier_str: &str = identifier.as_ref();
-            if !stack.locals.contains_key(identifier_str) {
-                return Err(Error::UndefinedVariable(identifier.to_string()));
-            }
-
-            generate_expr(expression, stack, codegen, pass)?;
-
-            // pop the result of the expression off the stack
-            stack_pop!(codegen, Register::R0);
-
-            // Due to the above check, this should never fail
-            if let Some(location) = stack.locals.get(identifier_str) {
-                assign_variable!(codegen, stack, location, Register::R0);
-            }
+            let register = local(identifier, builder)?;
+            lower_expr_into(expression, register, builder, codegen)
+        }
+        Statement::CompoundAssignment {
+            identifier,
+            operator,
+            expression,
+        } => {
+            builder.push(Op::Comment(format!(
+                "Compound assignment: {identifier:?} {operator:?} {expression:?}"
+            )));
 
-            Ok(())
+            // update the variable in place
+            let register = local(identifier, builder)?;
+            let update = Expr::BinaryOp(
+                Box::new(Expr::Identifier(identifier.clone())),
+                *operator,
+                expression.clone(),
+            );
+            lower_expr_into(&update, register, builder, codegen)
         }
         Statement::Definition {
             identifier,
             expression,
         } => {
-            codegen.add_comment_line(format!("Definition: {identifier:?} {expression:?}"));
-            // generate code for value expression
-            generate_expr(expression, stack, codegen, pass)?;
+            builder.push(Op::Comment(format!(
+                "Definition: {identifier:?} {expression:?}"
+            )));
+
+            // constants are looked up before locals, so a local of the same name would be ignored
+            if codegen.is_constant(identifier) {
+                return Err(Error::ConstantRedefined(identifier.to_string()));
+            }
 
-            // Allocate space for local variable
-            stack.allocate_local_at(identifier.to_string(), Location::Stack(-1));
+            // Defining a local again reuses its register. A new local only becomes visible once
+            // its value has been computed.
+            let identifier_str: &str = identifier.as_ref();
+            match builder.locals.get(identifier_str).copied() {
+                Some(register) => lower_expr_into(expression, register, builder, codegen)?,
+                None => {
+                    let register = builder.new_register();
+                    lower_expr_into(expression, register, builder, codegen)?;
+                    builder.locals.insert(identifier.to_string(), register);
+                }
+            }
 
             Ok(())
         }
-        Statement::Alias { identifier, alias } => {
+        Statement::Alias {
+            docs,
+            identifier,
+            alias,
+        } => {
             let identifier_ref: &str = identifier.as_ref();
-            codegen.add_alias(alias.clone(), Device::from_str(identifier_ref)?);
+            let device = Device::from_str(identifier_ref)?;
+            codegen.add_alias(alias.clone(), device);
 
             // TODO: We don't need to emit an instruction as long as we track the alias during
             // codegen. This could be made optional to reduce final code size.
-            codegen.add_instruction(
-                Misc::Alias {
-                    name: alias.to_string(),
-                    target: identifier.to_string(),
-                }
-                .into(),
-            );
+            builder.push(Op::Alias {
+                name: alias.to_string(),
+                device,
+                docs: docs.clone(),
+            });
 
             Ok(())
         }
         Statement::Constant(identifier, value) => {
-            codegen.add_constant(identifier.clone(), *value);
+            let name: &str = identifier.as_ref();
+            if builder.locals.contains_key(name) {
+                return Err(Error::ConstantRedefined(identifier.to_string()));
+            }
+            let value = evaluate_constant(value, codegen)?;
+            codegen.add_constant(identifier.clone(), value);
 
             Ok(())
         }
-        Statement::Function {
+        Statement::Function { identifier, .. } => {
+            Err(Error::NestedFunction(identifier.to_string()))
+        }
+        Statement::FunctionCall {
             identifier,
-            parameters,
-            body,
+            arguments,
         } => {
-            codegen.add_label(identifier.to_string());
-            codegen.add_comment(format!("Function: {identifier:?} {parameters:?}"));
-
-            if let Pass::Second = pass {
-                // function prologue
-
-                let body = Statement::Block(body.clone());
-
-                // allocate space for function parameters
-                for (i, parameter) in parameters.iter().enumerate() {
-                    if i < 4 {
-                        // receive parameter from register
-                        let register = Register::from(i as u8);
-                        stack_push!(codegen, register);
-                        codegen.add_comment(format!("parameter {parameter:?} from {register:?}"));
-                        stack
-                            .allocate_local_at(parameter.to_string(), Location::Register(register));
-                    } else {
-                        // receive parameter from stack
-                        // the stack increases upwards, so we
-                        let offset = (parameters.len() - i) as i32;
-                        stack.allocate_local_at(parameter.to_string(), Location::Stack(offset));
-                        codegen.add_comment_line(format!(
-                            "parameter {parameter:?} from stack offset {offset:?}"
-                        ));
-                    }
-                }
+            let name: &str = identifier.as_ref();
+            if let Some(intrinsic) = Intrinsic::from_name(name) {
+                // the result is not used
+                lower_intrinsic(intrinsic, identifier, arguments, None, builder, codegen)?;
+                return Ok(());
+            }
 
-                // allocate locals
-                let mut locals = Vec::new();
-                find_locals(&body, &mut locals);
-                for local in &locals {
-                    stack_push!(codegen, Number::Int(0));
-                    codegen.add_comment(format!("local {local:?}"));
-                    stack.allocate_local_at(local.to_string(), Location::Stack(-1));
-                }
+            lower_function_call(identifier, arguments, None, builder, codegen)
+        }
+        Statement::Block(block) => lower_block(block, builder, codegen),
+        Statement::Loop { label, body } => {
+            let name = builder.new_loop();
+            let loop_block = builder.new_block(name.clone());
+            let end_block = builder.new_block(format!("{name}_end"));
 
-                // function body
-                generate_statement(&body, stack, codegen, pass)?;
+            builder.switch_to(loop_block);
+            builder.start_loop(label.as_ref(), loop_block, end_block);
+            lower_block(body, builder, codegen)?;
+            builder.end_loop();
 
-                // function epilogue
+            // jump back to the start of the loop
+            builder.terminate(Terminator::Jump(loop_block));
+            builder.switch_to(end_block);
 
-                // deallocate locals
-                for local in locals {
-                    stack.deallocate_local(local.to_string());
-                }
+            Ok(())
+        }
+        Statement::While {
+            label,
+            condition,
+            body,
+        } => {
+            let name = builder.new_loop();
+            let loop_block = builder.new_block(name.clone());
+            let body_block = builder.new_block(format!("{name}_body"));
+            let end_block = builder.new_block(format!("{name}_end"));
 
-                // deallocate parameters
-                for parameter in parameters {
-                    stack.deallocate_local(parameter.to_string());
-                }
-                function_return!(codegen);
-            } else {
-                // reserve space using placeholder instructions
-                // allocate space for function parameters
-                for (i, parameter) in parameters.iter().enumerate() {
-                    if i < 4 {
-                        // receive parameter from register
-                        let register = Register::from(i as u8);
-                        stack
-                            .allocate_local_at(parameter.to_string(), Location::Register(register));
-                    } else {
-                        // receive parameter from stack
-                        let offset = (parameters.len() - i) as i32;
-                        stack.allocate_local_at(parameter.to_string(), Location::Stack(offset));
-                    }
-                }
+            // leave the loop once the condition is false
+            builder.switch_to(loop_block);
+            let condition = lower_condition(condition, builder, codegen)?;
+            builder.terminate(Terminator::Branch {
+                condition,
+                then: body_block,
+                otherwise: end_block,
+            });
 
-                let body = Statement::Block(body.clone());
+            builder.switch_to(body_block);
+            builder.start_loop(label.as_ref(), loop_block, end_block);
+            lower_block(body, builder, codegen)?;
+            builder.end_loop();
 
-                // allocate locals
-                let mut locals = Vec::new();
-                find_locals(&body, &mut locals);
-                for local in &locals {
-                    stack_push!(codegen, Number::Int(0));
-                    codegen.add_comment(format!("local {local:?}"));
-                    stack.allocate_local(local.to_string());
-                }
+            // jump back to the condition
+            builder.terminate(Terminator::Jump(loop_block));
+            builder.switch_to(end_block);
 
-                // function body
-                generate_statement(&body, stack, codegen, pass)?;
+            Ok(())
+        }
+        Statement::For {
+            label,
+            variable,
+            range,
+            body,
+        } => {
+            builder.push(Op::Comment(format!("For: {variable:?} {range:?}")));
 
-                // function epilogue
+            // a constant step decides the direction of the loop at compile time
+            let constant_step = match &range.step {
+                Some(step) => match evaluate_constant(step, codegen) {
+                    Ok(Value::Integer(step)) if step != 0 => Some(step),
+                    Ok(_) => return Err(Error::InvalidStep(format!("{step:?}"))),
+                    Err(_) => None,
+                },
+                None => Some(1),
+            };
 
-                // deallocate locals
-                for local in locals {
-                    stack.deallocate_local(local.to_string());
-                }
+            // the bounds are evaluated once, before the first iteration
+            let register = builder.new_register();
+            lower_expr_into(&range.start, register, builder, codegen)?;
 
-                // deallocate parameters
-                for parameter in parameters {
-                    stack.deallocate_local(parameter.to_string());
+            // variables can change while the loop runs, so a bound that is not constant is copied
+            let end: Operand = match evaluate_constant(&range.end, codegen) {
+                Ok(_) => lower_expr(&range.end, builder, codegen)?,
+                Err(_) => {
+                    let end = builder.new_register();
+                    lower_expr_into(&range.end, end, builder, codegen)?;
+                    end.into()
                 }
-                function_return!(codegen);
-            }
+            };
 
-            Ok(())
-        }
-        Statement::FunctionCall {
-            identifier,
-            arguments,
-        } => {
-            // pass arguments
-            for (i, argument) in arguments.iter().enumerate() {
-                generate_expr(argument, stack, codegen, pass)?;
-                if i < 4 {
-                    // pass argument as register
-                    let register = Register::from(i as u8);
-                    stack_pop!(codegen, register);
-                } else {
-                    // pass argument on the stack
-                    // this is already done by generate_expr
+            let step: Operand = match (constant_step, &range.step) {
+                (Some(step), _) => constant_operand(Value::Integer(step)).into(),
+                (None, Some(step)) => {
+                    let register = builder.new_register();
+                    lower_expr_into(step, register, builder, codegen)?;
+                    register.into()
                 }
-            }
+                (None, None) => unreachable!("a missing step is 1"),
+            };
+            let condition = ForCondition {
+                variable: register,
+                end,
+                step: step.clone(),
+                ascending: constant_step.map(|step| step > 0),
+                inclusive: range.inclusive,
+            };
 
-            // save registers
-            codegen.add_comment_line("saving registers".to_string());
-            for register in &[
-                Register::Ra,
-                Register::R4,
-                Register::R5,
-                Register::R6,
-                Register::R7,
-            ] {
-                stack.save_register(*register, codegen);
+            if codegen.is_constant(variable) {
+                return Err(Error::ConstantRedefined(variable.to_string()));
             }
+            let variable_name = variable.to_string();
+            let shadowed = builder.locals.get(&variable_name).copied();
+            builder.locals.insert(variable_name.clone(), register);
 
-            // call function
-            if let Pass::Second = pass {
-                let target_line = codegen.get_label(identifier.as_ref())?;
-                codegen.add_instruction(FlowControl::JumpAndLink { a: target_line }.into());
-                codegen.add_comment(format!("FunctionCall: {identifier:?} {arguments:?}"));
-            } else {
-                // reserve space for the second pass by adding a placeholder instruction
-                codegen.add_instruction(FlowControl::JumpAndLink { a: 0 }.into());
-            }
+            let name = builder.new_loop();
+            let loop_block = builder.new_block(name.clone());
+            let next_block = builder.new_block(format!("{name}_next"));
+            let end_block = builder.new_block(format!("{name}_end"));
 
-            // restore saved registers
-            for register in &[
-                Register::R7,
-                Register::R6,
-                Register::R5,
-                Register::R4,
-                Register::Ra,
-            ] {
-                stack.restore_register(*register, codegen);
+            // skip the loop entirely if the range is empty. A step of 0 would never reach the end
+            if constant_step.is_none() {
+                let check_block = builder.new_block(format!("{name}_check"));
+                builder.terminate(Terminator::Branch {
+                    condition: Condition::Compare {
+                        comparison: Comparison::Equal,
+                        a: step.clone(),
+                        b: Number::Int(0).into(),
+                    },
+                    then: end_block,
+                    otherwise: check_block,
+                });
+                builder.switch_to(check_block);
             }
+            let entry_condition = condition.lower(builder);
+            builder.terminate(Terminator::Branch {
+                condition: entry_condition,
+                then: loop_block,
+                otherwise: end_block,
+            });
 
-            // deallocate arguments
-            for (i, _arg) in arguments.iter().enumerate() {
-                if i >= 4 {
-                    stack_pop!(codegen, Register::R0);
-                }
-            }
+            // `continue` still advances the loop variable
+            builder.switch_to(loop_block);
+            builder.start_loop(label.as_ref(), next_block, end_block);
+            lower_block(body, builder, codegen)?;
+            builder.end_loop();
 
-            Ok(())
-        }
-        Statement::Block(block) => {
-            match block {
-                Block::Statements(statements) => {
-                    for statement in statements {
-                        generate_statement(statement, stack, codegen, pass)?;
-                    }
-                }
-            }
+            builder.switch_to(next_block);
+            builder.push(Op::Binary {
+                dest: register,
+                op: ayysee_ir::BinaryOp::Add,
+                a: register.into(),
+                b: step,
+            });
+            let condition = condition.lower(builder);
+            builder.terminate(Terminator::Branch {
+                condition,
+                then: loop_block,
+                otherwise: end_block,
+            });
+
+            builder.switch_to(end_block);
+
+            match shadowed {
+                Some(register) => builder.locals.insert(variable_name, register),
+                None => builder.locals.remove(&variable_name),
+            };
 
             Ok(())
         }
-        Statement::Loop { body } => {
-            let loop_label = stack.new_loop();
-
-            codegen.add_label(loop_label.clone());
+        Statement::Break(label) => {
+            let target = builder
+                .find_loop(label.as_ref())
+                .map(|active| active.break_block)
+                .ok_or_else(|| loop_error("break", label.as_ref()))?;
 
-            generate_statement(&Statement::Block(body.clone()), stack, codegen, pass)?;
+            builder.terminate(Terminator::Jump(target));
 
-            // jump back to the start of the loop
-            if let Pass::Second = pass {
-                let line = codegen.get_label(&loop_label)?;
-                codegen.add_instruction(FlowControl::Jump { a: line }.into());
-            } else {
-                // reserve space for the second pass by adding a placeholder instruction
-                codegen.add_instruction(FlowControl::Jump { a: 0 }.into());
-            }
+            Ok(())
+        }
+        Statement::Continue(label) => {
+            let target = builder
+                .find_loop(label.as_ref())
+                .map(|active| active.continue_block)
+                .ok_or_else(|| loop_error("continue", label.as_ref()))?;
 
-            stack.end_loop();
+            builder.terminate(Terminator::Jump(target));
 
             Ok(())
         }
@@ -277,35 +303,22 @@ pub(crate) fn generate_statement(
             match if_statement {
                 IfStatement::If { condition, body } => {
                     // handle if without else
-                    // evaluate the condition. If it is false, jump to the end of the if statement
-                    generate_expr(condition, stack, codegen, pass)?;
-
-                    // pop the condition from the stack
-                    stack_pop!(codegen, Register::R0);
-
-                    let if_label = stack.new_if();
-                    let end_label = format!("{}_end", if_label);
-
-                    // jump to the end of the if statement if the condition is false
-                    if let Pass::Second = pass {
-                        let line = codegen.get_label(&end_label)?;
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchEqualZero {
-                            a: Register::R0.into(),
-                            b: Number::Int(line).into(),
-                        }));
-                    } else {
-                        // reserve space for the second pass by adding a placeholder instruction
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchEqualZero {
-                            a: Register::R0.into(),
-                            b: Number::Int(0).into(),
-                        }));
-                    }
+                    let name = builder.new_if();
+                    let then_block = builder.new_block(name.clone());
+                    let end_block = builder.new_block(format!("{name}_end"));
+
+                    // evaluate the condition. If it is false, skip to the end of the if statement
+                    let condition = lower_condition(condition, builder, codegen)?;
+                    builder.terminate(Terminator::Branch {
+                        condition,
+                        then: then_block,
+                        otherwise: end_block,
+                    });
 
-                    // generate the if body
-                    generate_statement(&Statement::Block(body.clone()), stack, codegen, pass)?;
+                    builder.switch_to(then_block);
+                    lower_block(body, builder, codegen)?;
 
-                    // add label for end of if statement
-                    codegen.add_label(end_label);
+                    builder.switch_to(end_block);
                 }
                 IfStatement::IfElse {
                     condition,
@@ -313,94 +326,43 @@ pub(crate) fn generate_statement(
                     else_body,
                 } => {
                     // handle if with else
-                    generate_expr(condition, stack, codegen, pass)?;
-
-                    // pop the condition from the stack
-                    stack_pop!(codegen, Register::R0);
-
-                    let if_label = stack.new_if();
-                    let else_label = format!("{}_else", if_label);
-                    let end_label = format!("{}_end", if_label);
-
-                    // jump to the else statement if the condition is false
-                    if let Pass::Second = pass {
-                        let line = codegen.get_label(&else_label)?;
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchEqualZero {
-                            a: Register::R0.into(),
-                            b: Number::Int(line).into(),
-                        }));
-                    } else {
-                        // reserve space for the second pass by adding a placeholder instruction
-                        codegen.add_instruction(Instruction::from(FlowControl::BranchEqualZero {
-                            a: Register::R0.into(),
-                            b: Number::Int(0).into(),
-                        }));
-                    }
+                    let name = builder.new_if();
+                    let then_block = builder.new_block(name.clone());
+                    let else_block = builder.new_block(format!("{name}_else"));
+                    let end_block = builder.new_block(format!("{name}_end"));
 
-                    // generate the if body
-                    generate_statement(&Statement::Block(body.clone()), stack, codegen, pass)?;
+                    // continue with the else body if the condition is false
+                    let condition = lower_condition(condition, builder, codegen)?;
+                    builder.terminate(Terminator::Branch {
+                        condition,
+                        then: then_block,
+                        otherwise: else_block,
+                    });
 
-                    // jump to end of if statement
-                    if let Pass::Second = pass {
-                        let line = codegen.get_label(&end_label)?;
-                        codegen.add_instruction(Instruction::from(FlowControl::Jump { a: line }));
-                    } else {
-                        // reserve space for the second pass by adding a placeholder instruction
-                        codegen.add_instruction(Instruction::from(FlowControl::Jump { a: 0 }));
-                    }
-
-                    // add label for else statement
-                    codegen.add_label(else_label);
+                    builder.switch_to(then_block);
+                    lower_block(body, builder, codegen)?;
+                    builder.terminate(Terminator::Jump(end_block));
 
-                    // generate the else body
-                    generate_statement(&Statement::Block(else_body.clone()), stack, codegen, pass)?;
+                    builder.switch_to(else_block);
+                    lower_block(else_body, builder, codegen)?;
 
-                    // add label for end of if statement
-                    codegen.add_label(end_label);
+                    builder.switch_to(end_block);
                 }
             }
 
             Ok(())
         }
+        Statement::Match { expression, arms } => lower_match(expression, arms, builder, codegen),
         Statement::DeviceStatement(device_statement) => {
             match device_statement {
                 ayysee_parser::ast::DeviceStatement::Read {
                     device,
                     device_variable,
-                    local,
+                    local: identifier,
                 } => {
-                    let local: &str = local.as_ref();
-                    if !stack.locals.contains_key(local) {
-                        return Err(Error::UndefinedVariable(local.to_string()));
-                    }
-
-                    if let Pass::Second = pass {
-                        let device = codegen.get_device(device)?.unwrap();
-
-                        let variable: &str = device_variable.as_ref();
-                        let variable = DeviceVariable::from_str(variable)?;
-                        // Load the device variable into a register
-                        codegen.add_instruction(Instruction::from(DeviceIo::LoadDeviceVariable {
-                            device,
-                            variable,
-                            register: Register::R0,
-                        }));
-
-                        if let Some(location) = stack.locals.get(local) {
-                            assign_variable!(codegen, stack, location, Register::R0);
-                        }
-                    } else {
-                        // reserve space for the second pass by adding a placeholder instruction
-                        codegen.add_instruction(Instruction::from(DeviceIo::LoadDeviceVariable {
-                            device: Device::D0,
-                            variable: DeviceVariable::Setting,
-                            register: Register::R0,
-                        }));
-
-                        if let Some(location) = stack.locals.get(local) {
-                            assign_variable!(codegen, stack, location, Register::R0);
-                        }
-                    }
+                    let register = local(identifier, builder)?;
+                    let read = Expr::DeviceRead(device.clone(), device_variable.clone());
+                    lower_expr_into(&read, register, builder, codegen)?;
                 }
 
                 ayysee_parser::ast::DeviceStatement::Write {
@@ -408,67 +370,781 @@ pub(crate) fn generate_statement(
                     device,
                     device_variable,
                 } => {
-                    generate_expr(value, stack, codegen, pass)?;
-
-                    // pop the value from the stack
-                    stack_pop!(codegen, Register::R0);
-
-                    if let Pass::Second = pass {
-                        let device = codegen.get_device(device)?.unwrap();
-
-                        let variable: &str = device_variable.as_ref();
-                        let variable = DeviceVariable::from_str(variable)?;
-                        // Load the device variable into a register
-                        codegen.add_instruction(Instruction::from(DeviceIo::StoreDeviceVariable {
-                            device,
-                            variable,
-                            register: Register::R0,
-                        }));
-                    } else {
-                        // reserve space for the second pass by adding a placeholder instruction
-                        codegen.add_instruction(Instruction::from(DeviceIo::StoreDeviceVariable {
-                            device: Device::D0,
-                            variable: DeviceVariable::Setting,
-                            register: Register::R0,
-                        }));
+                    let value = lower_expr(value, builder, codegen)?;
+                    let device = codegen.resolve_device(device)?;
+                    let variable: &str = device_variable.as_ref();
+                    let variable = DeviceVariable::from_str(variable)?;
+                    builder.push(Op::StoreDevice {
+                        device,
+                        variable,
+                        value,
+                    });
+                }
+
+                ayysee_parser::ast::DeviceStatement::SlotWrite {
+                    value,
+                    device,
+                    slot,
+                    slot_variable,
+                } => {
+                    let variable: &str = slot_variable.as_ref();
+                    let variable = SlotVariable::from_str(variable)?;
+                    if !variable.is_writable() {
+                        return Err(Error::ReadOnlySlotVariable(variable.to_string()));
                     }
+
+                    let value = lower_expr(value, builder, codegen)?;
+                    let slot = lower_expr(slot, builder, codegen)?;
+                    let device = codegen.resolve_device(device)?;
+                    builder.push(Op::StoreSlot {
+                        device,
+                        slot,
+                        variable,
+                        value,
+                    });
+                }
+
+                ayysee_parser::ast::DeviceStatement::BatchWrite {
+                    value,
+                    device_type,
+                    device_variable,
+                } => {
+                    let value = lower_expr(value, builder, codegen)?;
+                    let type_hash = resolve_type_hash(device_type, codegen)?;
+                    let variable: &str = device_variable.as_ref();
+                    let variable = DeviceVariable::from_str(variable)?;
+                    // Store the value in the variable of every device of the type
+                    builder.push(Op::StoreBatch {
+                        type_hash,
+                        variable,
+                        value,
+                    });
                 }
             }
 
             Ok(())
         }
+        Statement::Require(devices) => {
+            builder.push(Op::Comment(format!("require {devices:?}")));
+
+            for device in devices {
+                let device = codegen.resolve_device(device)?;
+                builder.push(Op::Require { device });
+            }
+
+            Ok(())
+        }
         Statement::Yield => {
-            codegen.add_instruction(Instruction::from(Misc::Yield));
+            builder.push(Op::Yield);
+
+            Ok(())
+        }
+        Statement::Return(value) => {
+            if !builder.in_function {
+                return Err(Error::ReturnOutsideFunction);
+            }
+
+            let value = match value {
+                Some(value) => Some(lower_expr(value, builder, codegen)?),
+                None => None,
+            };
+            builder.terminate(Terminator::Return(value));
 
             Ok(())
         }
     }
 }
 
-/// Finds all of the locals used in a statement
-fn find_locals(statement: &Statement, locals: &mut Vec<Identifier>) {
-    match statement {
-        Statement::Definition { identifier, .. } => {
-            if !locals.contains(identifier) {
-                locals.push(identifier.clone());
+/// Lowers the statements of a block.
+fn lower_block(block: &Block, builder: &mut Builder, codegen: &mut CodeGenerator) -> Result<()> {
+    match block {
+        Block::Statements(statements) => {
+            for statement in statements {
+                lower_statement(statement, builder, codegen)?;
             }
         }
-        Statement::Block(block) => match block {
-            Block::Statements(statements) => {
-                for statement in statements {
-                    find_locals(statement, locals);
-                }
+    }
+
+    Ok(())
+}
+
+/// Lowers a function definition into a function of its own.
+/// The parameters are received in the first registers of the function.
+pub(crate) fn lower_function(
+    statement: &Statement,
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<Function> {
+    let Statement::Function {
+        docs,
+        identifier,
+        parameters,
+        body,
+    } = statement
+    else {
+        unreachable!("only functions are lowered into functions")
+    };
+
+    builder.start_function(identifier.to_string(), true);
+    for parameter in parameters {
+        if codegen.is_constant(parameter) {
+            return Err(Error::ConstantRedefined(parameter.to_string()));
+        }
+        let register = builder.new_register();
+        builder.locals.insert(parameter.to_string(), register);
+        builder.function().parameters.push(register);
+    }
+
+    lower_block(body, builder, codegen)?;
+
+    let mut function = builder.finish_function();
+    function.docs = docs.clone();
+    function.returns_value = codegen
+        .get_function(identifier)
+        .is_some_and(|signature| signature.returns_value);
+
+    // the caller would read whatever happens to be left in r0
+    if function.returns_value && can_return_without_value(&mut function) {
+        return Err(Error::MissingReturnValue(identifier.to_string()));
+    }
+
+    Ok(function)
+}
+
+/// Checks whether a function can return without a value, either with `return;` or by running off
+/// the end of its body. Branches on constants are folded first, so that e.g. the end of a
+/// `while true` loop does not count.
+fn can_return_without_value(function: &mut Function) -> bool {
+    for block in &mut function.blocks {
+        block.terminator.fold_constant();
+    }
+
+    let reachable = Cfg::new(function).reachable();
+    function
+        .blocks
+        .iter()
+        .zip(reachable)
+        .any(|(block, reachable)| reachable && matches!(block.terminator, Terminator::Return(None)))
+}
+
+/// The condition that keeps a `for` loop going.
+struct ForCondition {
+    variable: VReg,
+    end: Operand,
+    step: Operand,
+    /// Whether the step is positive, if it is known at compile time
+    ascending: Option<bool>,
+    inclusive: bool,
+}
+
+impl ForCondition {
+    /// Lowers the condition, pushing the operations it needs into the current block.
+    fn lower(&self, builder: &mut Builder) -> Condition {
+        if let Some(ascending) = self.ascending {
+            let comparison = match (ascending, self.inclusive) {
+                (true, false) => Comparison::Less,
+                (true, true) => Comparison::LessOrEqual,
+                (false, false) => Comparison::Greater,
+                (false, true) => Comparison::GreaterOrEqual,
+            };
+            return Condition::Compare {
+                comparison,
+                a: self.variable.into(),
+                b: self.end.clone(),
+            };
+        }
+
+        // the direction is only known at runtime. The variable has not passed the end as long as
+        // its distance to the end has the opposite sign of the step
+        let distance = builder.new_register();
+        builder.push(Op::Binary {
+            dest: distance,
+            op: ayysee_ir::BinaryOp::Sub,
+            a: self.variable.into(),
+            b: self.end.clone(),
+        });
+        let product = builder.new_register();
+        builder.push(Op::Binary {
+            dest: product,
+            op: ayysee_ir::BinaryOp::Mul,
+            a: distance.into(),
+            b: self.step.clone(),
+        });
+        Condition::Compare {
+            comparison: if self.inclusive {
+                Comparison::LessOrEqual
+            } else {
+                Comparison::Less
+            },
+            a: product.into(),
+            b: Number::Int(0).into(),
+        }
+    }
+}
+
+/// Calls a function, keeping its return value in `destination` if one is given.
+pub(crate) fn lower_function_call(
+    identifier: &Identifier,
+    arguments: &[Box<Expr>],
+    destination: Option<VReg>,
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<()> {
+    let signature = codegen
+        .get_function(identifier)
+        .ok_or_else(|| Error::UndefinedFunction(identifier.to_string()))?;
+    if signature.parameters != arguments.len() {
+        return Err(Error::WrongArgumentCount {
+            function: identifier.to_string(),
+            expected: signature.parameters,
+            found: arguments.len(),
+        });
+    }
+
+    let mut operands = Vec::new();
+    for argument in arguments {
+        operands.push(lower_expr(argument, builder, codegen)?);
+    }
+
+    builder.push(Op::Call {
+        dest: destination,
+        function: identifier.to_string(),
+        arguments: operands,
+    });
+
+    Ok(())
+}
+
+/// Lowers a `match` statement into a switch over the values of its arms.
+fn lower_match(
+    expression: &Expr,
+    arms: &[MatchArm],
+    builder: &mut Builder,
+    codegen: &mut CodeGenerator,
+) -> Result<()> {
+    let name = builder.new_match();
+    let arm_blocks: Vec<_> = (0..arms.len())
+        .map(|i| builder.new_block(format!("{name}_{i}")))
+        .collect();
+    let end_block = builder.new_block(format!("{name}_end"));
+
+    // resolve the patterns to the values they match
+    let mut cases = Vec::new();
+    let mut default = None;
+    for (i, arm) in arms.iter().enumerate() {
+        if default.is_some() {
+            return Err(Error::UnreachableMatchArm);
+        }
+
+        let value = match &arm.pattern {
+            MatchPattern::Integer(value) => *value,
+            MatchPattern::Constant(identifier) => match codegen.get_constant(identifier) {
+                Some(Value::Integer(value)) => value,
+                Some(Value::Boolean(value)) => value as i64,
+                _ => return Err(Error::InvalidMatchPattern(identifier.to_string())),
+            },
+            MatchPattern::Wildcard => {
+                default = Some(arm_blocks[i]);
+                continue;
             }
-        },
-        Statement::FunctionCall { arguments, .. } => {
-            for argument in arguments {
-                if let Expr::Identifier(identifier) = *argument.clone() {
-                    if !locals.contains(&identifier) {
-                        locals.push(identifier.clone());
+        };
+
+        // IC10 numbers are doubles, which cannot tell larger integers apart
+        if value.unsigned_abs() > 1 << 53 {
+            return Err(Error::MatchPatternOutOfRange(value));
+        }
+        if cases.iter().any(|(case, _)| *case == value) {
+            return Err(Error::DuplicateMatchPattern(value));
+        }
+        cases.push((value, arm_blocks[i]));
+    }
+
+    builder.push(Op::Comment(format!("Match: {expression:?}")));
+    let value = lower_expr(expression, builder, codegen)?;
+    builder.terminate(Terminator::Switch {
+        value,
+        cases,
+        default: default.unwrap_or(end_block),
+    });
+
+    for (arm, block) in arms.iter().zip(arm_blocks) {
+        builder.switch_to(block);
+        lower_block(&arm.body, builder, codegen)?;
+        builder.terminate(Terminator::Jump(end_block));
+    }
+
+    builder.switch_to(end_block);
+
+    Ok(())
+}
+
+/// Records the signature of a function so that calls can be checked before it is lowered.
+pub(crate) fn declare_function(statement: &Statement, codegen: &mut CodeGenerator) -> Result<()> {
+    if let Statement::Function {
+        identifier,
+        parameters,
+        body,
+        ..
+    } = statement
+    {
+        let name: &str = identifier.as_ref();
+        if Intrinsic::from_name(name).is_some() {
+            return Err(Error::ReservedName(identifier.to_string()));
+        }
+        if codegen.get_function(identifier).is_some() {
+            return Err(Error::DuplicateFunction(identifier.to_string()));
+        }
+
+        let body = Statement::Block(body.clone());
+        codegen.add_function(
+            identifier.clone(),
+            FunctionSignature {
+                parameters: parameters.len(),
+                returns_value: returns_value(&body),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks whether a statement contains a `return` with a value.
+fn returns_value(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(value) => value.is_some(),
+        Statement::Block(Block::Statements(statements)) => statements.iter().any(returns_value),
+        Statement::Loop { body, .. }
+        | Statement::While { body, .. }
+        | Statement::For { body, .. } => returns_value(&Statement::Block(body.clone())),
+        Statement::IfStatement(IfStatement::If { body, .. }) => {
+            returns_value(&Statement::Block(body.clone()))
+        }
+        Statement::IfStatement(IfStatement::IfElse {
+            body, else_body, ..
+        }) => {
+            returns_value(&Statement::Block(body.clone()))
+                || returns_value(&Statement::Block(else_body.clone()))
+        }
+        Statement::Match { arms, .. } => arms
+            .iter()
+            .any(|arm| returns_value(&Statement::Block(arm.body.clone()))),
+        _ => false,
+    }
+}
+
+/// Finds the register of a local variable.
+fn local(identifier: &Identifier, builder: &Builder) -> Result<VReg> {
+    let identifier_str: &str = identifier.as_ref();
+    builder
+        .locals
+        .get(identifier_str)
+        .copied()
+        .ok_or_else(|| Error::UndefinedVariable(identifier.to_string()))
+}
+
+/// Creates the error for a `break` or `continue` that has no loop to refer to.
+fn loop_error(statement: &str, label: Option<&Identifier>) -> Error {
+    match label {
+        Some(label) => Error::UndefinedLoopLabel(label.to_string()),
+        None => Error::OutsideLoop(statement.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::{Emulator, Exit};
+    use crate::{
+        error::Error,
+        testing::{compile, compile_error},
+    };
+
+    #[test]
+    fn test_break_and_continue() {
+        let program = compile(
+            "fn main() {
+                let total = 0;
+                let i = 0;
+                'outer: loop {
+                    i = i + 1;
+                    if i > 10 { break; }
+                    let j = 0;
+                    while j < 10 {
+                        j = j + 1;
+                        if j == 2 { continue; }
+                        if j == 4 { continue 'outer; }
+                        if i == 10 { break 'outer; }
+                        total = total + j;
                     }
                 }
+                d0.Setting = total;
+            }",
+        );
+
+        // `continue` goes back to the condition of the inner loop, the labelled ones leave it
+        assert_eq!(program.after("bne r6 2 main.if_1_end"), "j main.loop_1");
+        assert_eq!(program.after("bne r6 4 main.if_2_end"), "j main.loop_0");
+        assert_eq!(
+            program.after("bne r5 10 main.if_3_end"),
+            "j main.loop_0_end"
+        );
+        assert_eq!(program.after("main.loop_0_end:"), "s d0 Setting r4");
+
+        // every outer iteration adds 1 and 3 until the tenth leaves both loops
+        assert_eq!(program.writes(&[]), ["d0 Setting 36"]);
+
+        assert!(matches!(
+            compile_error("fn main() { break; }"),
+            Error::OutsideLoop(_)
+        ));
+        assert!(matches!(
+            compile_error("fn main() { loop { break 'missing; } }"),
+            Error::UndefinedLoopLabel(_)
+        ));
+    }
+
+    #[test]
+    fn test_function_returns() {
+        let program = compile(
+            "fn sign(a) {
+                if a > 0 { return 1; } else if a < 0 { return -1; }
+                return 0;
+            }
+            fn first_above(limit) {
+                let i = 0;
+                loop {
+                    i = i + 1;
+                    if i * i > limit { return i; }
+                }
             }
+            fn main() {
+                d0.Setting = sign(d0.Temperature);
+                d1.Setting = first_above(d0.Temperature);
+            }",
+        );
+        let run = |t| program.writes(&[("d0 Temperature", t)]);
+        assert_eq!(run(10.0), ["d0 Setting 1", "d1 Setting 4"]);
+        assert_eq!(run(-3.0), ["d0 Setting -1", "d1 Setting 1"]);
+
+        // the caller would read a value that was never written
+        for body in [
+            "if a > 0 { return 1; }",
+            "while a > 0 { return 1; }",
+            "if a > 0 { return 1; } return;",
+        ] {
+            let source = format!("fn f(a) {{ {body} }} fn main() {{ d0.Setting = f(1); }}");
+            assert!(
+                matches!(compile_error(&source), Error::MissingReturnValue(name) if name == "f"),
+                "{body}"
+            );
+        }
+
+        assert!(matches!(
+            compile_error("fn main() {} fn main() {}"),
+            Error::DuplicateFunction(name) if name == "main"
+        ));
+    }
+
+    #[test]
+    fn test_else_if_and_match() {
+        let program = compile(
+            "fn main() {
+                let mode = d0.Mode;
+                if mode == 1 {
+                    d1.Setting = 10;
+                } else if mode == 2 {
+                    d1.Setting = 20;
+                } else {
+                    d1.Setting = 30;
+                }
+            }",
+        );
+        assert_eq!(
+            program.lines[3..6],
+            ["l r4 d0 Mode", "bne r4 1 main.if_0_else", "move r1 10"]
+        );
+        assert!(program.contains("bne r4 2 main.if_1_else"));
+
+        let run = |mode| program.writes(&[("d0 Mode", mode)]);
+        assert_eq!(run(1.0), ["d1 Setting 10"]);
+        assert_eq!(run(2.0), ["d1 Setting 20"]);
+        assert_eq!(run(3.0), ["d1 Setting 30"]);
+
+        assert!(matches!(
+            compile_error("fn main() { match d0.Mode { 1 => {}, 1 => {}, } }"),
+            Error::DuplicateMatchPattern(1)
+        ));
+        assert!(matches!(
+            compile_error("fn main() { match d0.Mode { _ => {}, 1 => {}, } }"),
+            Error::UnreachableMatchArm
+        ));
+        // a double cannot tell this pattern from 2^53
+        assert!(matches!(
+            compile_error("fn main() { match d0.Mode { 9007199254740993 => {}, } }"),
+            Error::MatchPatternOutOfRange(9007199254740993)
+        ));
+    }
+
+    #[test]
+    fn test_device_reads_and_writes() {
+        let program = compile(
+            "def d0 as GasSensor;
+            def d1 as Heater;
+            const MinTemp = 290;
+            fn main() {
+                if GasSensor.Temperature < MinTemp {
+                    Heater.Setting = GasSensor.Temperature + 10;
+                }
+                Heater.On = GasSensor.Temperature < MinTemp;
+            }",
+        );
+        assert_eq!(
+            program.lines[5..],
+            [
+                "l r4 d0 Temperature",
+                "bge r4 290 main.if_0_end",
+                "l r4 d0 Temperature",
+                "add r4 r4 10",
+                "s d1 Setting r4",
+                "main.if_0_end:",
+                "l r4 d0 Temperature",
+                "slt r4 r4 290",
+                "s d1 On r4",
+                "j ra",
+            ]
+        );
+
+        let run = |t| program.writes(&[("d0 Temperature", t)]);
+        assert_eq!(run(280.0), ["d1 Setting 290", "d1 On 1"]);
+        assert_eq!(run(300.0), ["d1 On 0"]);
+    }
+
+    #[test]
+    fn test_batch_io() {
+        let program = compile(
+            "fn main() {
+                let avg = batch(StructureGasSensor).Temperature.Average;
+                batch(StructureWallHeater).On = avg < 290;
+                batch(HASH(\"StructureWallHeater\")).Lock = batch(StructureGasSensor).Pressure.Maximum;
+            }",
+        );
+        assert_eq!(
+            program.lines[3..],
+            [
+                "lb r4 -1252983604 Temperature Average",
+                "slt r4 r4 290",
+                "sb 24258244 On r4",
+                "lb r4 -1252983604 Pressure Maximum",
+                "sb 24258244 Lock r4",
+                "j ra",
+            ]
+        );
+
+        let writes = program.writes(&[
+            ("-1252983604 Temperature Average", 280.0),
+            ("-1252983604 Pressure Maximum", 101.0),
+        ]);
+        assert_eq!(writes, ["24258244 On 1", "24258244 Lock 101"]);
+    }
+
+    #[test]
+    fn test_slot_and_reagent_io() {
+        let program = compile(
+            "def d0 as Sorter;
+            def d1 as Furnace;
+            fn main() {
+                let i = 1;
+                Sorter.slot[0].Lock = Sorter.slot[i].Occupied;
+                d2.Setting = Sorter.slot[0].OccupantHash;
+                d2.On = Furnace.reagent(Contents, Iron);
+            }",
+        );
+        assert_eq!(
+            program.lines[5..],
+            [
+                "move r4 1",
+                "ls r4 d0 r4 Occupied",
+                "ss d0 0 Lock r4",
+                "ls r4 d0 0 OccupantHash",
+                "s d2 Setting r4",
+                "lr r4 d1 Contents -666742878",
+                "s d2 On r4",
+                "j ra",
+            ]
+        );
+
+        let writes = program.writes(&[
+            ("d0 1 Occupied", 1.0),
+            ("d0 0 OccupantHash", 1234.0),
+            ("d1 Contents -666742878", 12.5),
+        ]);
+        assert_eq!(writes, ["d0 0 Lock 1", "d2 Setting 1234", "d2 On 12.5"]);
+
+        assert!(matches!(
+            compile_error("fn main() { d0.slot[0].Occupied = 1; }"),
+            Error::ReadOnlySlotVariable(_)
+        ));
+    }
+
+    #[test]
+    fn test_connection_checks() {
+        let program = compile(
+            "def d0 as GasSensor;
+            require GasSensor, d1;
+            fn main() {
+                if connected(d2) {
+                    d2.On = 1;
+                }
+                d1.Setting = connected(d3);
+            }",
+        );
+        assert_eq!(
+            program.lines[1..5],
+            ["brdse d0 2", "hcf", "brdse d1 2", "hcf"]
+        );
+        assert!(program.contains("bdns d2 main.if_0_end"));
+        assert!(program.contains("sdse r4 d3"));
+
+        assert_eq!(program.writes(&[]), ["d2 On 1", "d1 Setting 1"]);
+        let run = program.run_on(&Emulator::new().disconnect("d2").disconnect("d3"));
+        assert_eq!(run.writes, ["d1 Setting 0"]);
+
+        // a missing required device stops the program before main runs
+        let run = program.run_on(&Emulator::new().disconnect("d1"));
+        assert_eq!(run.exit, Exit::Halted);
+        assert!(run.writes.is_empty());
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        // a variable in a register is updated in place
+        let program = compile(
+            "fn main() {
+                let x = d0.Setting;
+                x += 2; x -= 1; x *= 3; x /= 2; x %= 5; x &= 7; x |= 8; x++; x--;
+                d1.Setting = x;
+            }",
+        );
+        assert_eq!(
+            program.lines[4..13],
+            [
+                "add r4 r4 2",
+                "sub r4 r4 1",
+                "mul r4 r4 3",
+                "div r4 r4 2",
+                "mod r4 r4 5",
+                "and r4 r4 7",
+                "or r4 r4 8",
+                "add r4 r4 1",
+                "sub r4 r4 1",
+            ]
+        );
+        // ((3 + 2 - 1) * 3 / 2) % 5 = 1, & 7 = 1, | 8 = 9
+        assert_eq!(program.writes(&[("d0 Setting", 3.0)]), ["d1 Setting 9"]);
+
+        // a device variable is read, updated and written back
+        let program = compile(
+            "def d1 as Heater;
+            fn main() {
+                Heater.Setting += 5;
+                Heater.On--;
+            }",
+        );
+        assert_eq!(
+            program.lines[4..10],
+            [
+                "l r4 d1 Setting",
+                "add r4 r4 5",
+                "s d1 Setting r4",
+                "l r4 d1 On",
+                "sub r4 r4 1",
+                "s d1 On r4",
+            ]
+        );
+        let writes = program.writes(&[("d1 Setting", 290.0), ("d1 On", 1.0)]);
+        assert_eq!(writes, ["d1 Setting 295", "d1 On 0"]);
+    }
+
+    #[test]
+    fn test_for_loops() {
+        // constant bounds and steps use a single compare-and-branch
+        let program = compile(
+            "fn main() {
+                for i in (0..10).step(3) { d1.Setting = i; }
+            }",
+        );
+        assert_eq!(
+            program.lines[3..],
+            [
+                "move r4 0",
+                "bge r4 10 main.loop_0_end",
+                "main.loop_0:",
+                "s d1 Setting r4",
+                "add r4 r4 3",
+                "blt r4 10 main.loop_0",
+                "main.loop_0_end:",
+                "j ra",
+            ]
+        );
+        assert_eq!(
+            program.writes(&[]),
+            [
+                "d1 Setting 0",
+                "d1 Setting 3",
+                "d1 Setting 6",
+                "d1 Setting 9"
+            ]
+        );
+
+        // steps that do not fit in 32 bits are added exactly, and decide the direction by
+        // their sign
+        let program = compile(
+            "fn main() {
+                for i in (0..10_000_000_000).step(4294967296) { d1.Setting = i; }
+                for i in (0..=-4294967297).step(-4294967296) { d1.On = i; }
+            }",
+        );
+        assert!(program.contains("add r4 r4 4294967296"));
+        assert!(program.contains("add r4 r4 -4294967296"));
+        assert_eq!(
+            program.writes(&[]),
+            [
+                "d1 Setting 0",
+                "d1 Setting 4294967296",
+                "d1 Setting 8589934592",
+                "d1 On 0",
+                "d1 On -4294967296",
+            ]
+        );
+
+        // a step that is only known at runtime can go either way, and changing the variable it
+        // was read from does not affect the loop
+        let program = compile(
+            "fn main() {
+                let k = d0.Setting;
+                let total = 0;
+                for i in (d0.On..=20).step(k) {
+                    total += i;
+                    k = 100;
+                }
+                d1.Setting = total;
+            }",
+        );
+        let run = |start, step| {
+            let run = program.run(&[("d0 On", start), ("d0 Setting", step)]);
+            assert_eq!(run.exit, Exit::Halted);
+            run.writes
+        };
+        assert_eq!(run(10.0, 5.0), ["d1 Setting 45"]);
+        assert_eq!(run(30.0, -5.0), ["d1 Setting 75"]);
+        assert_eq!(run(10.0, -5.0), ["d1 Setting 0"]);
+        assert_eq!(run(10.0, 0.0), ["d1 Setting 0"]);
+
+        for step in ["0", "1.5", "true"] {
+            let source = format!("fn main() {{ for i in (0..10).step({step}) {{}} }}");
+            assert!(
+                matches!(compile_error(&source), Error::InvalidStep(_)),
+                "{step}"
+            );
         }
-        _ => {}
     }
 }
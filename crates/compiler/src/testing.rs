@@ -0,0 +1,107 @@
+//! Compiling whole programs in tests and running them in the emulator.
+
+use crate::{
+    emulator::{Emulator, Run},
+    error::{Error, Result},
+    generate_program_with_options, Options,
+};
+use ayysee_parser::grammar::ProgramParser;
+
+/// A program compiled for a test.
+pub(crate) struct Compiled {
+    /// The code as it would be pasted into the game, with jumps resolved to line numbers
+    pub(crate) code: String,
+    /// The instructions and labels of the code with labels kept, without comments and blank
+    /// lines, which is easier to compare
+    pub(crate) lines: Vec<String>,
+}
+
+impl Compiled {
+    /// Whether the program contains an instruction or label.
+    pub(crate) fn contains(&self, line: &str) -> bool {
+        self.lines.iter().any(|other| other == line)
+    }
+
+    /// Whether any instruction uses an opcode.
+    pub(crate) fn uses(&self, opcode: &str) -> bool {
+        self.lines
+            .iter()
+            .any(|line| line.split_whitespace().next() == Some(opcode))
+    }
+
+    /// The line that follows an instruction or label.
+    pub(crate) fn after(&self, line: &str) -> &str {
+        let position = self
+            .lines
+            .iter()
+            .position(|other| other == line)
+            .unwrap_or_else(|| panic!("{line} is not in {:?}", self.lines));
+        &self.lines[position + 1]
+    }
+
+    /// Runs the program against devices that hold the given values, e.g.
+    /// `("d0 Temperature", 300.0)`.
+    pub(crate) fn run(&self, values: &[(&str, f64)]) -> Run {
+        let emulator = values
+            .iter()
+            .fold(Emulator::new(), |emulator, (key, value)| {
+                emulator.set(key, *value)
+            });
+        self.run_on(&emulator)
+    }
+
+    /// Runs the program against devices that were set up by hand, e.g. to disconnect a pin.
+    pub(crate) fn run_on(&self, emulator: &Emulator) -> Run {
+        emulator.run(&self.code)
+    }
+
+    /// The writes to devices of a run against the given values.
+    pub(crate) fn writes(&self, values: &[(&str, f64)]) -> Vec<String> {
+        self.run(values).writes
+    }
+}
+
+/// Compiles a program with the default options.
+pub(crate) fn compile(source: &str) -> Compiled {
+    compile_with(source, &Options::default())
+        .unwrap_or_else(|error| panic!("{source} does not compile: {error}"))
+}
+
+/// Compiles a program. `keep_labels` is ignored, as both forms of the code are kept.
+pub(crate) fn compile_with(source: &str, options: &Options) -> Result<Compiled> {
+    let code = generate(source, options)?;
+    let lines = generate(
+        source,
+        &Options {
+            keep_labels: true,
+            ..options.clone()
+        },
+    )?
+    .lines()
+    .map(|line| {
+        line.split('#')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    })
+    .filter(|line| !line.is_empty())
+    .collect();
+
+    Ok(Compiled { code, lines })
+}
+
+/// Compiles a program that must be rejected, and returns the error.
+pub(crate) fn compile_error(source: &str) -> Error {
+    match generate(source, &Options::default()) {
+        Ok(code) => panic!("{source} compiles to {code}"),
+        Err(error) => error,
+    }
+}
+
+fn generate(source: &str, options: &Options) -> Result<String> {
+    let program = ProgramParser::new()
+        .parse(source)
+        .unwrap_or_else(|error| panic!("{source} does not parse: {error}"));
+    generate_program_with_options(program, options)
+}
@@ -0,0 +1,136 @@
+use crate::{codegen::CodeGenerator, constant::evaluate_constant};
+use ayysee_parser::ast::{BinaryOpcode, Dimension, Expr, Identifier, UnaryOpcode, Value};
+
+/// Works out the dimension of the value an expression produces, if it is known.
+/// Unit literals carry their dimension, and device variables are recognised by their name, e.g.
+/// `TemperatureInput` is a temperature.
+pub(crate) fn dimension_of(expr: &Expr, codegen: &CodeGenerator) -> Option<Dimension> {
+    if let Ok(value) = evaluate_constant(expr, codegen) {
+        return match value {
+            Value::Quantity(_, unit) => Some(unit.dimension()),
+            _ => None,
+        };
+    }
+
+    match expr {
+        Expr::DeviceRead(_, variable)
+        | Expr::BatchRead(_, variable, _)
+        | Expr::SlotRead(_, _, variable) => variable_dimension(variable),
+        Expr::UnaryOp(UnaryOpcode::Neg, operand) => dimension_of(operand, codegen),
+        Expr::BinaryOp(left, BinaryOpcode::Add | BinaryOpcode::Sub, right) => {
+            match (dimension_of(left, codegen), dimension_of(right, codegen)) {
+                (Some(a), Some(b)) => (a == b).then_some(a),
+                (a, b) => a.or(b),
+            }
+        }
+        Expr::Select(_, then, otherwise) => {
+            dimension_of(then, codegen).or_else(|| dimension_of(otherwise, codegen))
+        }
+        _ => None,
+    }
+}
+
+fn variable_dimension(variable: &Identifier) -> Option<Dimension> {
+    let name: &str = variable.as_ref();
+    if name.starts_with("Temperature") {
+        Some(Dimension::Temperature)
+    } else if name.starts_with("Pressure") {
+        Some(Dimension::Pressure)
+    } else {
+        None
+    }
+}
+
+/// Warns about comparisons between values of different dimensions, such as a temperature read
+/// compared to a pressure literal.
+pub(crate) fn lint_comparison(expr: &Expr, codegen: &CodeGenerator) {
+    if let Some(warning) = comparison_warning(expr, codegen) {
+        tracing::warn!("{warning}");
+    }
+}
+
+/// The warning about a comparison, if it compares values of different dimensions.
+fn comparison_warning(expr: &Expr, codegen: &CodeGenerator) -> Option<String> {
+    let Expr::BinaryOp(left, op, right) = expr else {
+        return None;
+    };
+    let comparison = matches!(
+        op,
+        BinaryOpcode::Equals
+            | BinaryOpcode::NotEquals
+            | BinaryOpcode::Greater
+            | BinaryOpcode::GreaterEquals
+            | BinaryOpcode::Lower
+            | BinaryOpcode::LowerEquals
+    );
+    if !comparison {
+        return None;
+    }
+
+    match (dimension_of(left, codegen), dimension_of(right, codegen)) {
+        (Some(a), Some(b)) if a != b => Some(format!("comparing a {a:?} to a {b:?} in {expr:?}")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::compile;
+    use ayysee_parser::grammar::ExprParser;
+
+    fn warning(expression: &str) -> Option<String> {
+        let expr = ExprParser::new().parse(expression).unwrap();
+        comparison_warning(&expr, &CodeGenerator::new())
+    }
+
+    #[test]
+    fn test_comparison_lint() {
+        for expression in [
+            "d0.Temperature > 101kPa",
+            "d0.PressureInternal < 20C",
+            "batch(StructureGasSensor).Temperature.Average == 1MPa",
+            "-d0.Temperature != 5Pa",
+            "d0.Temperature + 5K >= 1kPa",
+            "20C <= 1kPa",
+        ] {
+            let warning = warning(expression);
+            assert!(
+                warning
+                    .as_deref()
+                    .is_some_and(|warning| warning.starts_with("comparing a ")),
+                "{expression}: {warning:?}"
+            );
+        }
+
+        // the same dimension, a plain number or an unknown dimension on either side is fine
+        for expression in [
+            "d0.Temperature > 20C",
+            "d0.Pressure < 101.325kPa",
+            "20F == 250K",
+            "d0.Temperature > 300",
+            "d0.Setting > 1kPa",
+            "d0.Temperature + 1kPa > 20C",
+            "d0.Temperature + 20C",
+        ] {
+            assert_eq!(warning(expression), None, "{expression}");
+        }
+    }
+
+    #[test]
+    fn test_unit_literals() {
+        // temperatures are converted to Kelvin and pressures to kPa
+        for (literal, value) in [
+            ("20C", "293.15"),
+            ("-20C", "253.15"),
+            ("68F", "293.15"),
+            ("300K", "300"),
+            ("101.325kPa", "101.325"),
+            ("1_000Pa", "1"),
+            ("2MPa", "2000"),
+        ] {
+            let program = compile(&format!("fn main() {{ d1.Setting = {literal}; }}"));
+            assert_eq!(program.lines[3], format!("move r1 {value}"), "{literal}");
+        }
+    }
+}
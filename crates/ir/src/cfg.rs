@@ -0,0 +1,131 @@
+use crate::program::{BlockId, Function};
+
+/// The control-flow graph of a function: which blocks can follow which.
+pub struct Cfg {
+    successors: Vec<Vec<BlockId>>,
+    predecessors: Vec<Vec<BlockId>>,
+    entry: BlockId,
+}
+
+impl Cfg {
+    pub fn new(function: &Function) -> Self {
+        let successors: Vec<Vec<BlockId>> = function
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut successors: Vec<BlockId> = Vec::new();
+                for successor in block.terminator.successors() {
+                    if !successors.contains(&successor) {
+                        successors.push(successor);
+                    }
+                }
+                successors
+            })
+            .collect();
+
+        let mut predecessors = vec![Vec::new(); function.blocks.len()];
+        for (block, targets) in function.block_ids().zip(&successors) {
+            for target in targets {
+                if !predecessors[target.0].contains(&block) {
+                    predecessors[target.0].push(block);
+                }
+            }
+        }
+
+        Self {
+            successors,
+            predecessors,
+            entry: function.entry(),
+        }
+    }
+
+    /// The blocks that execution can continue in after a block.
+    pub fn successors(&self, block: BlockId) -> &[BlockId] {
+        &self.successors[block.0]
+    }
+
+    /// The blocks that execution can come from before a block.
+    pub fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        &self.predecessors[block.0]
+    }
+
+    /// The blocks in the order a depth-first search from the entry finishes them.
+    /// Blocks that cannot be reached from the entry are left out.
+    pub fn postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.successors.len()];
+        let mut order = Vec::new();
+        if self.successors.is_empty() {
+            return order;
+        }
+
+        // each entry is a block and the index of the next successor to visit
+        let mut stack = vec![(self.entry, 0)];
+        visited[self.entry.0] = true;
+        while let Some((block, next)) = stack.last_mut() {
+            let block = *block;
+            match self.successors[block.0].get(*next) {
+                Some(successor) => {
+                    *next += 1;
+                    if !visited[successor.0] {
+                        visited[successor.0] = true;
+                        stack.push((*successor, 0));
+                    }
+                }
+                None => {
+                    order.push(block);
+                    stack.pop();
+                }
+            }
+        }
+
+        order
+    }
+
+    /// Whether each block can be reached from the entry.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.successors.len()];
+        for block in self.postorder() {
+            reachable[block.0] = true;
+        }
+
+        reachable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        program::BasicBlock,
+        terminator::{Comparison, Condition, Terminator},
+    };
+    use stationeers_mips::types::Number;
+
+    #[test]
+    fn test_cfg() {
+        // bb0 -> bb1 -> (bb1 | bb2), bb3 is never reached
+        let mut function = Function::new("main".to_string());
+        let counter = function.new_register();
+        for name in ["entry", "loop", "end", "dead"] {
+            function.blocks.push(BasicBlock::new(name.to_string()));
+        }
+        function.blocks[0].terminator = Terminator::Jump(BlockId(1));
+        function.blocks[1].terminator = Terminator::Branch {
+            condition: Condition::Compare {
+                comparison: Comparison::Less,
+                a: counter.into(),
+                b: Number::Int(10).into(),
+            },
+            then: BlockId(1),
+            otherwise: BlockId(2),
+        };
+        function.blocks[3].terminator = Terminator::Jump(BlockId(2));
+
+        let cfg = Cfg::new(&function);
+        assert_eq!(cfg.successors(BlockId(1)), &[BlockId(1), BlockId(2)]);
+        assert_eq!(cfg.predecessors(BlockId(1)), &[BlockId(0), BlockId(1)]);
+        assert_eq!(cfg.predecessors(BlockId(2)), &[BlockId(1), BlockId(3)]);
+        assert_eq!(cfg.postorder(), vec![BlockId(2), BlockId(1), BlockId(0)]);
+        assert_eq!(cfg.reachable(), vec![true, true, true, false]);
+    }
+}
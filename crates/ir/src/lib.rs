@@ -0,0 +1,21 @@
+/// This library is the intermediate representation that the ayysee compiler lowers programs into
+/// before emitting Stationeers MIPS.
+///
+/// # Usage
+///
+/// A `Program` is made of functions, and each `Function` is a list of basic blocks. A block is a
+/// straight run of `Op`s that ends in a single `Terminator`, which is the only place where control
+/// flow happens. Values live in an unlimited number of virtual registers that are mapped onto
+/// machine registers and the stack when MIPS is emitted.
+///
+/// The `cfg` and `liveness` modules provide the analyses that passes over the IR are built on.
+/// Every type implements the `Display` trait, so programs can be printed for inspection.
+pub mod cfg;
+pub mod liveness;
+pub mod op;
+pub mod program;
+pub mod terminator;
+
+pub use op::{BinaryOp, Op, UnaryOp};
+pub use program::{BasicBlock, BlockId, Function, Operand, Program, VReg};
+pub use terminator::{Comparison, Condition, Terminator};
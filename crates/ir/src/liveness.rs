@@ -0,0 +1,159 @@
+use crate::{
+    cfg::Cfg,
+    program::{BlockId, Function, VReg},
+};
+use std::collections::BTreeSet;
+
+/// Which registers hold a value that may still be read.
+/// A register is live at a point if some path from there reads it before writing it.
+pub struct Liveness {
+    live_in: Vec<BTreeSet<VReg>>,
+    live_out: Vec<BTreeSet<VReg>>,
+}
+
+impl Liveness {
+    pub fn new(function: &Function, cfg: &Cfg) -> Self {
+        // the registers each block reads before writing them, and the ones it writes
+        let (uses, defs): (Vec<BTreeSet<VReg>>, Vec<BTreeSet<VReg>>) = function
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut uses = BTreeSet::new();
+                let mut defs = BTreeSet::new();
+                for op in &block.ops {
+                    uses.extend(op.uses().into_iter().filter(|used| !defs.contains(used)));
+                    defs.extend(op.dest());
+                }
+                uses.extend(
+                    block
+                        .terminator
+                        .uses()
+                        .into_iter()
+                        .filter(|used| !defs.contains(used)),
+                );
+                (uses, defs)
+            })
+            .unzip();
+
+        let mut live_in = vec![BTreeSet::new(); function.blocks.len()];
+        let mut live_out = vec![BTreeSet::new(); function.blocks.len()];
+
+        // iterate backwards until nothing changes, which needs few rounds as values flow against
+        // the layout order
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in function.block_ids().rev() {
+                let out: BTreeSet<VReg> = cfg
+                    .successors(block)
+                    .iter()
+                    .flat_map(|successor| live_in[successor.0].iter().copied())
+                    .collect();
+                let mut inside = uses[block.0].clone();
+                inside.extend(out.difference(&defs[block.0]));
+
+                if inside != live_in[block.0] || out != live_out[block.0] {
+                    changed = true;
+                    live_in[block.0] = inside;
+                    live_out[block.0] = out;
+                }
+            }
+        }
+
+        Self { live_in, live_out }
+    }
+
+    /// The registers that are live when a block is entered.
+    pub fn live_in(&self, block: BlockId) -> &BTreeSet<VReg> {
+        &self.live_in[block.0]
+    }
+
+    /// The registers that are live when a block is left.
+    pub fn live_out(&self, block: BlockId) -> &BTreeSet<VReg> {
+        &self.live_out[block.0]
+    }
+
+    /// The registers that are live right after each operation of a block.
+    pub fn live_after_ops(&self, function: &Function, block: BlockId) -> Vec<BTreeSet<VReg>> {
+        let block_ref = function.block(block);
+        let mut live = self.live_out[block.0].clone();
+        live.extend(block_ref.terminator.uses());
+
+        let mut live_after = vec![BTreeSet::new(); block_ref.ops.len()];
+        for (i, op) in block_ref.ops.iter().enumerate().rev() {
+            live_after[i] = live.clone();
+            if let Some(dest) = op.dest() {
+                live.remove(&dest);
+            }
+            live.extend(op.uses());
+        }
+
+        live_after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        op::{BinaryOp, Op},
+        program::BasicBlock,
+        terminator::{Comparison, Condition, Terminator},
+    };
+    use stationeers_mips::types::Number;
+
+    #[test]
+    fn test_liveness() {
+        // %0 counts up in a loop and %1 is only needed inside of it
+        let mut function = Function::new("main".to_string());
+        let counter = function.new_register();
+        let step = function.new_register();
+        for name in ["entry", "loop", "end"] {
+            function.blocks.push(BasicBlock::new(name.to_string()));
+        }
+        function.blocks[0].ops = vec![
+            Op::Move {
+                dest: counter,
+                source: Number::Int(0).into(),
+            },
+            Op::Move {
+                dest: step,
+                source: Number::Int(2).into(),
+            },
+        ];
+        function.blocks[0].terminator = Terminator::Jump(BlockId(1));
+        function.blocks[1].ops = vec![Op::Binary {
+            dest: counter,
+            op: BinaryOp::Add,
+            a: counter.into(),
+            b: step.into(),
+        }];
+        function.blocks[1].terminator = Terminator::Branch {
+            condition: Condition::Compare {
+                comparison: Comparison::Less,
+                a: counter.into(),
+                b: Number::Int(10).into(),
+            },
+            then: BlockId(1),
+            otherwise: BlockId(2),
+        };
+        function.blocks[2].terminator = Terminator::Return(Some(counter.into()));
+
+        let cfg = Cfg::new(&function);
+        let liveness = Liveness::new(&function, &cfg);
+        assert!(liveness.live_in(BlockId(0)).is_empty());
+        assert_eq!(
+            liveness.live_in(BlockId(1)),
+            &BTreeSet::from([counter, step])
+        );
+        assert_eq!(
+            liveness.live_out(BlockId(1)),
+            &BTreeSet::from([counter, step])
+        );
+        assert_eq!(liveness.live_in(BlockId(2)), &BTreeSet::from([counter]));
+
+        let live_after = liveness.live_after_ops(&function, BlockId(0));
+        assert_eq!(live_after[0], BTreeSet::from([counter]));
+        assert_eq!(live_after[1], BTreeSet::from([counter, step]));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::{
+    program::{Operand, VReg},
+    terminator::Comparison,
+};
+use stationeers_mips::types::{
+    BatchMode, Device, DeviceVariable, Reagent, ReagentMode, SlotVariable, TypeHash,
+};
+
+/// An operation on two values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// The remainder, which always has the sign of the divisor
+    Mod,
+    And,
+    Or,
+    Xor,
+    Nor,
+    ShiftLeft,
+    /// Shifts right, keeping the sign
+    ShiftRightArithmetic,
+    /// Shifts right, filling with zeros
+    ShiftRightLogical,
+    Min,
+    Max,
+    /// 1 if the comparison holds, otherwise 0
+    Compare(Comparison),
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "add"),
+            BinaryOp::Sub => write!(f, "sub"),
+            BinaryOp::Mul => write!(f, "mul"),
+            BinaryOp::Div => write!(f, "div"),
+            BinaryOp::Mod => write!(f, "mod"),
+            BinaryOp::And => write!(f, "and"),
+            BinaryOp::Or => write!(f, "or"),
+            BinaryOp::Xor => write!(f, "xor"),
+            BinaryOp::Nor => write!(f, "nor"),
+            BinaryOp::ShiftLeft => write!(f, "sll"),
+            BinaryOp::ShiftRightArithmetic => write!(f, "sra"),
+            BinaryOp::ShiftRightLogical => write!(f, "srl"),
+            BinaryOp::Min => write!(f, "min"),
+            BinaryOp::Max => write!(f, "max"),
+            BinaryOp::Compare(comparison) => write!(f, "{comparison}"),
+        }
+    }
+}
+
+/// An operation on a single value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Abs,
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Exp,
+    Log,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+    /// 1 if the value is 0, otherwise 0
+    IsZero,
+    /// 1 if the value is not 0, otherwise 0
+    NotZero,
+}
+
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UnaryOp::Abs => write!(f, "abs"),
+            UnaryOp::Sqrt => write!(f, "sqrt"),
+            UnaryOp::Sin => write!(f, "sin"),
+            UnaryOp::Cos => write!(f, "cos"),
+            UnaryOp::Tan => write!(f, "tan"),
+            UnaryOp::Asin => write!(f, "asin"),
+            UnaryOp::Acos => write!(f, "acos"),
+            UnaryOp::Atan => write!(f, "atan"),
+            UnaryOp::Exp => write!(f, "exp"),
+            UnaryOp::Log => write!(f, "log"),
+            UnaryOp::Floor => write!(f, "floor"),
+            UnaryOp::Ceil => write!(f, "ceil"),
+            UnaryOp::Round => write!(f, "round"),
+            UnaryOp::Trunc => write!(f, "trunc"),
+            UnaryOp::IsZero => write!(f, "iszero"),
+            UnaryOp::NotZero => write!(f, "notzero"),
+        }
+    }
+}
+
+/// A single operation inside a basic block.
+/// Operations never transfer control, except that a call returns to the next operation.
+#[derive(Clone, Debug)]
+pub enum Op {
+    Move {
+        dest: VReg,
+        source: Operand,
+    },
+    Binary {
+        dest: VReg,
+        op: BinaryOp,
+        a: Operand,
+        b: Operand,
+    },
+    Unary {
+        dest: VReg,
+        op: UnaryOp,
+        a: Operand,
+    },
+    /// `then` if the condition is not 0, otherwise `otherwise`
+    Select {
+        dest: VReg,
+        condition: Operand,
+        then: Operand,
+        otherwise: Operand,
+    },
+    /// A random number between 0 and 1
+    Random {
+        dest: VReg,
+    },
+    /// Calls a function and stores its return value in `dest`, if it is kept.
+    Call {
+        dest: Option<VReg>,
+        function: String,
+        arguments: Vec<Operand>,
+    },
+    /// Gives a device pin a name in the output. Device accesses always refer to the pin itself.
+    Alias {
+        name: String,
+        device: Device,
+        /// `///` comments from the source
+        docs: Vec<String>,
+    },
+    LoadDevice {
+        dest: VReg,
+        device: Device,
+        variable: DeviceVariable,
+    },
+    StoreDevice {
+        device: Device,
+        variable: DeviceVariable,
+        value: Operand,
+    },
+    LoadSlot {
+        dest: VReg,
+        device: Device,
+        slot: Operand,
+        variable: SlotVariable,
+    },
+    StoreSlot {
+        device: Device,
+        slot: Operand,
+        variable: SlotVariable,
+        value: Operand,
+    },
+    LoadReagent {
+        dest: VReg,
+        device: Device,
+        mode: ReagentMode,
+        reagent: Reagent,
+    },
+    /// Reads a variable of every device of a type on the network, combined with `mode`
+    LoadBatch {
+        dest: VReg,
+        type_hash: TypeHash,
+        variable: DeviceVariable,
+        mode: BatchMode,
+    },
+    /// Writes a variable of every device of a type on the network
+    StoreBatch {
+        type_hash: TypeHash,
+        variable: DeviceVariable,
+        value: Operand,
+    },
+    /// 1 if a device is connected to the pin, otherwise 0
+    Connected {
+        dest: VReg,
+        device: Device,
+    },
+    /// Halts the program unless a device is connected to the pin.
+    Require {
+        device: Device,
+    },
+    Yield,
+    /// A comment that is copied into the output on its own line
+    Comment(String),
+}
+
+impl Op {
+    /// The register that the operation writes, if any.
+    pub fn dest(&self) -> Option<VReg> {
+        match self {
+            Op::Move { dest, .. }
+            | Op::Binary { dest, .. }
+            | Op::Unary { dest, .. }
+            | Op::Select { dest, .. }
+            | Op::Random { dest }
+            | Op::LoadDevice { dest, .. }
+            | Op::LoadSlot { dest, .. }
+            | Op::LoadReagent { dest, .. }
+            | Op::LoadBatch { dest, .. }
+            | Op::Connected { dest, .. } => Some(*dest),
+            Op::Call { dest, .. } => *dest,
+            Op::StoreDevice { .. }
+            | Op::StoreSlot { .. }
+            | Op::StoreBatch { .. }
+            | Op::Alias { .. }
+            | Op::Require { .. }
+            | Op::Yield
+            | Op::Comment(_) => None,
+        }
+    }
+
+    /// The operands that the operation reads.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Op::Move { source, .. } => vec![source],
+            Op::Binary { a, b, .. } => vec![a, b],
+            Op::Unary { a, .. } => vec![a],
+            Op::Select {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => vec![condition, then, otherwise],
+            Op::Call { arguments, .. } => arguments.iter().collect(),
+            Op::StoreDevice { value, .. } | Op::StoreBatch { value, .. } => vec![value],
+            Op::LoadSlot { slot, .. } => vec![slot],
+            Op::StoreSlot { slot, value, .. } => vec![slot, value],
+            Op::Random { .. }
+            | Op::Alias { .. }
+            | Op::LoadDevice { .. }
+            | Op::LoadReagent { .. }
+            | Op::LoadBatch { .. }
+            | Op::Connected { .. }
+            | Op::Require { .. }
+            | Op::Yield
+            | Op::Comment(_) => Vec::new(),
+        }
+    }
+
+    /// The registers that the operation reads.
+    pub fn uses(&self) -> Vec<VReg> {
+        self.operands()
+            .into_iter()
+            .filter_map(Operand::register)
+            .collect()
+    }
+
+    /// The device that the operation accesses, if any.
+    /// An alias only gives a device a name, so it does not count as an access.
+    pub fn device(&self) -> Option<Device> {
+        match self {
+            Op::LoadDevice { device, .. }
+            | Op::StoreDevice { device, .. }
+            | Op::LoadSlot { device, .. }
+            | Op::StoreSlot { device, .. }
+            | Op::LoadReagent { device, .. }
+            | Op::Connected { device, .. }
+            | Op::Require { device } => Some(*device),
+            Op::Move { .. }
+            | Op::Binary { .. }
+            | Op::Unary { .. }
+            | Op::Select { .. }
+            | Op::Random { .. }
+            | Op::Call { .. }
+            | Op::Alias { .. }
+            | Op::LoadBatch { .. }
+            | Op::StoreBatch { .. }
+            | Op::Yield
+            | Op::Comment(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Op::Move { dest, source } => write!(f, "{dest} = move {source}"),
+            Op::Binary { dest, op, a, b } => write!(f, "{dest} = {op} {a} {b}"),
+            Op::Unary { dest, op, a } => write!(f, "{dest} = {op} {a}"),
+            Op::Select {
+                dest,
+                condition,
+                then,
+                otherwise,
+            } => write!(f, "{dest} = select {condition} {then} {otherwise}"),
+            Op::Random { dest } => write!(f, "{dest} = rand"),
+            Op::Call {
+                dest,
+                function,
+                arguments,
+            } => {
+                if let Some(dest) = dest {
+                    write!(f, "{dest} = ")?;
+                }
+                let arguments: Vec<String> = arguments.iter().map(Operand::to_string).collect();
+                write!(f, "call {function}({})", arguments.join(", "))
+            }
+            Op::Alias { name, device, .. } => write!(f, "alias {name} {device}"),
+            Op::LoadDevice {
+                dest,
+                device,
+                variable,
+            } => write!(f, "{dest} = load {device} {variable}"),
+            Op::StoreDevice {
+                device,
+                variable,
+                value,
+            } => write!(f, "store {device} {variable} {value}"),
+            Op::LoadSlot {
+                dest,
+                device,
+                slot,
+                variable,
+            } => write!(f, "{dest} = load {device} slot {slot} {variable}"),
+            Op::StoreSlot {
+                device,
+                slot,
+                variable,
+                value,
+            } => write!(f, "store {device} slot {slot} {variable} {value}"),
+            Op::LoadReagent {
+                dest,
+                device,
+                mode,
+                reagent,
+            } => write!(f, "{dest} = load {device} reagent {mode} {reagent}"),
+            Op::LoadBatch {
+                dest,
+                type_hash,
+                variable,
+                mode,
+            } => write!(f, "{dest} = load batch {type_hash} {variable} {mode}"),
+            Op::StoreBatch {
+                type_hash,
+                variable,
+                value,
+            } => write!(f, "store batch {type_hash} {variable} {value}"),
+            Op::Connected { dest, device } => write!(f, "{dest} = connected {device}"),
+            Op::Require { device } => write!(f, "require {device}"),
+            Op::Yield => write!(f, "yield"),
+            Op::Comment(comment) => write!(f, "# {comment}"),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{op::Op, terminator::Terminator};
+use stationeers_mips::types::Number;
+
+/// A virtual register.
+/// Functions can use as many of them as they like. They are not in SSA form, so a register that
+/// holds a variable is written every time the variable is assigned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VReg(pub u32);
+
+impl std::fmt::Display for VReg {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+/// A value that an operation reads.
+#[derive(Clone, Debug)]
+pub enum Operand {
+    Register(VReg),
+    Constant(Number),
+}
+
+impl Operand {
+    /// The register that is read, if the operand is not a constant.
+    pub fn register(&self) -> Option<VReg> {
+        match self {
+            Operand::Register(register) => Some(*register),
+            Operand::Constant(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "{}", register),
+            Operand::Constant(number) => write!(f, "{}", number),
+        }
+    }
+}
+
+impl From<VReg> for Operand {
+    fn from(register: VReg) -> Self {
+        Operand::Register(register)
+    }
+}
+
+impl From<Number> for Operand {
+    fn from(number: Number) -> Self {
+        Operand::Constant(number)
+    }
+}
+
+/// The index of a basic block within its function.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub usize);
+
+impl std::fmt::Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+/// A straight sequence of operations. Control only enters at the top and leaves through the
+/// terminator.
+#[derive(Clone, Debug)]
+pub struct BasicBlock {
+    /// A name for the block that is unique within the program, used for its label
+    pub name: String,
+    pub ops: Vec<Op>,
+    pub terminator: Terminator,
+}
+
+impl BasicBlock {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            ops: Vec::new(),
+            terminator: Terminator::Return(None),
+        }
+    }
+}
+
+/// A function and its control-flow graph.
+/// The first block is the entry, and the blocks are kept in the order they are laid out in.
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    /// `///` comments from the source
+    pub docs: Vec<String>,
+    /// The registers that receive the arguments, in order
+    pub parameters: Vec<VReg>,
+    /// Whether the function returns a value with `return <expr>;`
+    pub returns_value: bool,
+    pub blocks: Vec<BasicBlock>,
+    /// The number of virtual registers that have been created
+    pub registers: u32,
+}
+
+impl Function {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            docs: Vec::new(),
+            parameters: Vec::new(),
+            returns_value: false,
+            blocks: Vec::new(),
+            registers: 0,
+        }
+    }
+
+    /// The block that execution starts in.
+    pub fn entry(&self) -> BlockId {
+        BlockId(0)
+    }
+
+    /// Creates a new virtual register.
+    pub fn new_register(&mut self) -> VReg {
+        let register = VReg(self.registers);
+        self.registers += 1;
+
+        register
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id.0]
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> &mut BasicBlock {
+        &mut self.blocks[id.0]
+    }
+
+    /// The ids of all blocks, in layout order.
+    pub fn block_ids(&self) -> impl DoubleEndedIterator<Item = BlockId> {
+        (0..self.blocks.len()).map(BlockId)
+    }
+
+    /// Removes the blocks that are not kept, keeping the others in order.
+    /// No kept block may continue in a removed one.
+    pub fn retain_blocks(&mut self, keep: &[bool]) {
+        let mut position = Vec::with_capacity(self.blocks.len());
+        let mut next = 0;
+        for kept in keep {
+            position.push(BlockId(next));
+            if *kept {
+                next += 1;
+            }
+        }
+
+        let blocks = std::mem::take(&mut self.blocks);
+        self.blocks = blocks
+            .into_iter()
+            .zip(keep)
+            .filter(|(_, kept)| **kept)
+            .map(|(mut block, _)| {
+                block.terminator.map_successors(|target| position[target.0]);
+                block
+            })
+            .collect();
+    }
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for doc in &self.docs {
+            writeln!(f, "/// {doc}")?;
+        }
+
+        let parameters: Vec<String> = self.parameters.iter().map(VReg::to_string).collect();
+        write!(f, "fn {}({})", self.name, parameters.join(", "))?;
+        if self.returns_value {
+            write!(f, " -> value")?;
+        }
+        writeln!(f, " {{")?;
+
+        for (id, block) in self.block_ids().zip(&self.blocks) {
+            writeln!(f, "{id}: # {}", block.name)?;
+            for op in &block.ops {
+                writeln!(f, "    {op}")?;
+            }
+            writeln!(f, "    {}", block.terminator)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+/// A whole program.
+#[derive(Clone, Debug)]
+pub struct Program {
+    /// The top-level statements, which run first and end by calling `main`
+    pub init: Function,
+    pub functions: Vec<Function>,
+}
+
+impl Program {
+    /// Finds a function by name.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.init)?;
+        for function in &self.functions {
+            write!(f, "\n\n{function}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+use crate::program::{BlockId, Operand, VReg};
+use stationeers_mips::types::{Device, Number};
+
+/// A comparison between two values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    /// The comparison that holds exactly when this one does not.
+    pub fn invert(self) -> Self {
+        match self {
+            Comparison::Equal => Comparison::NotEqual,
+            Comparison::NotEqual => Comparison::Equal,
+            Comparison::Greater => Comparison::LessOrEqual,
+            Comparison::GreaterOrEqual => Comparison::Less,
+            Comparison::Less => Comparison::GreaterOrEqual,
+            Comparison::LessOrEqual => Comparison::Greater,
+        }
+    }
+
+    /// Whether the comparison holds between two numbers.
+    pub fn holds(self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterOrEqual => a >= b,
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+        }
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Comparison::Equal => write!(f, "eq"),
+            Comparison::NotEqual => write!(f, "ne"),
+            Comparison::Greater => write!(f, "gt"),
+            Comparison::GreaterOrEqual => write!(f, "ge"),
+            Comparison::Less => write!(f, "lt"),
+            Comparison::LessOrEqual => write!(f, "le"),
+        }
+    }
+}
+
+/// The condition of a branch.
+#[derive(Clone, Debug)]
+pub enum Condition {
+    Compare {
+        comparison: Comparison,
+        a: Operand,
+        b: Operand,
+    },
+    /// A device is connected to the pin
+    DeviceSet(Device),
+    /// No device is connected to the pin
+    DeviceNotSet(Device),
+}
+
+impl Condition {
+    /// The condition that holds exactly when this one does not.
+    pub fn invert(self) -> Self {
+        match self {
+            Condition::Compare { comparison, a, b } => Condition::Compare {
+                comparison: comparison.invert(),
+                a,
+                b,
+            },
+            Condition::DeviceSet(device) => Condition::DeviceNotSet(device),
+            Condition::DeviceNotSet(device) => Condition::DeviceSet(device),
+        }
+    }
+
+    /// The operands that the condition reads.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Condition::Compare { a, b, .. } => vec![a, b],
+            Condition::DeviceSet(_) | Condition::DeviceNotSet(_) => Vec::new(),
+        }
+    }
+
+    /// Whether the condition holds, if that is known without running the program.
+    pub fn evaluate(&self) -> Option<bool> {
+        match self {
+            Condition::Compare {
+                comparison,
+                a: Operand::Constant(a),
+                b: Operand::Constant(b),
+            } => Some(comparison.holds(as_float(a), as_float(b))),
+            _ => None,
+        }
+    }
+
+    /// The device that the condition checks, if any.
+    pub fn device(&self) -> Option<Device> {
+        match self {
+            Condition::DeviceSet(device) | Condition::DeviceNotSet(device) => Some(*device),
+            Condition::Compare { .. } => None,
+        }
+    }
+}
+
+fn as_float(number: &Number) -> f64 {
+    match number {
+        Number::Int(int) => *int as f64,
+        Number::Float(float) => *float,
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Condition::Compare { comparison, a, b } => write!(f, "{comparison} {a} {b}"),
+            Condition::DeviceSet(device) => write!(f, "connected {device}"),
+            Condition::DeviceNotSet(device) => write!(f, "disconnected {device}"),
+        }
+    }
+}
+
+/// The end of a basic block, which decides where execution continues.
+#[derive(Clone, Debug)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// Continues in `then` if the condition holds, otherwise in `otherwise`.
+    Branch {
+        condition: Condition,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+    /// Continues in the block of the case that equals the value, or in `default` if there is
+    /// none.
+    Switch {
+        value: Operand,
+        cases: Vec<(i64, BlockId)>,
+        default: BlockId,
+    },
+    /// Returns to the caller, passing back a value if one is given.
+    Return(Option<Operand>),
+    /// Stops the program.
+    Halt,
+    /// Starts the program again from the first line.
+    Restart,
+}
+
+impl Terminator {
+    /// The blocks that execution can continue in.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+            Terminator::Switch { cases, default, .. } => {
+                let mut successors: Vec<BlockId> = cases.iter().map(|(_, block)| *block).collect();
+                successors.push(*default);
+                successors
+            }
+            Terminator::Return(_) | Terminator::Halt | Terminator::Restart => Vec::new(),
+        }
+    }
+
+    /// Changes every reference to a block.
+    pub fn map_successors(&mut self, mut f: impl FnMut(BlockId) -> BlockId) {
+        match self {
+            Terminator::Jump(target) => *target = f(*target),
+            Terminator::Branch {
+                then, otherwise, ..
+            } => {
+                *then = f(*then);
+                *otherwise = f(*otherwise);
+            }
+            Terminator::Switch { cases, default, .. } => {
+                for (_, block) in cases.iter_mut() {
+                    *block = f(*block);
+                }
+                *default = f(*default);
+            }
+            Terminator::Return(_) | Terminator::Halt | Terminator::Restart => {}
+        }
+    }
+
+    /// The operands that the terminator reads.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Terminator::Branch { condition, .. } => condition.operands(),
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Return(Some(value)) => vec![value],
+            Terminator::Jump(_)
+            | Terminator::Return(None)
+            | Terminator::Halt
+            | Terminator::Restart => Vec::new(),
+        }
+    }
+
+    /// The registers that the terminator reads.
+    pub fn uses(&self) -> Vec<VReg> {
+        self.operands()
+            .into_iter()
+            .filter_map(Operand::register)
+            .collect()
+    }
+
+    /// Replaces a branch or switch whose outcome is already known with a jump to the block that
+    /// is always taken.
+    pub fn fold_constant(&mut self) {
+        let target = match self {
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => match condition.evaluate() {
+                Some(true) => *then,
+                Some(false) => *otherwise,
+                None => return,
+            },
+            Terminator::Switch {
+                value: Operand::Constant(value),
+                cases,
+                default,
+            } => {
+                let value = as_float(value);
+                cases
+                    .iter()
+                    .find(|(case, _)| *case as f64 == value)
+                    .map_or(*default, |(_, block)| *block)
+            }
+            _ => return,
+        };
+
+        *self = Terminator::Jump(target);
+    }
+}
+
+impl std::fmt::Display for Terminator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {target}"),
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => write!(f, "branch {condition} then {then} else {otherwise}"),
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let cases: Vec<String> = cases
+                    .iter()
+                    .map(|(case, block)| format!("{case}: {block}"))
+                    .collect();
+                write!(f, "switch {value} [{}] default {default}", cases.join(", "))
+            }
+            Terminator::Return(Some(value)) => write!(f, "return {value}"),
+            Terminator::Return(None) => write!(f, "return"),
+            Terminator::Halt => write!(f, "halt"),
+            Terminator::Restart => write!(f, "restart"),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+/// Computes the hash that Stationeers uses to identify prefabs and other named things.
+/// This is the CRC32 of the name, interpreted as a signed 32-bit integer, which is what the
+/// `HASH("...")` function in IC10 evaluates to.
+pub fn hash(name: &str) -> i32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in name.bytes() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash("StructureGasSensor"), -1252983604);
+        assert_eq!(hash(""), 0);
+    }
+}
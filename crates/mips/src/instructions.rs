@@ -1,3 +1,5 @@
+use crate::types::{Line, RegisterOrNumber};
+
 mod flow;
 mod io;
 mod logic;
@@ -26,6 +28,35 @@ pub enum Instruction {
     Misc(Misc),
 }
 
+impl Instruction {
+    /// The line a jump or branch goes to, unless it is relative.
+    pub fn target(&self) -> Option<&Line> {
+        match self {
+            Instruction::FlowControl(flow_control) => flow_control.target(),
+            Instruction::DeviceIo(device_io) => device_io.target(),
+            _ => None,
+        }
+    }
+
+    /// The line a jump or branch goes to, unless it is relative.
+    pub fn target_mut(&mut self) -> Option<&mut Line> {
+        match self {
+            Instruction::FlowControl(flow_control) => flow_control.target_mut(),
+            Instruction::DeviceIo(device_io) => device_io.target_mut(),
+            _ => None,
+        }
+    }
+
+    /// The distance from the current line that a relative jump or branch goes.
+    pub fn offset(&self) -> Option<&RegisterOrNumber> {
+        match self {
+            Instruction::FlowControl(flow_control) => flow_control.offset(),
+            Instruction::DeviceIo(device_io) => device_io.offset(),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
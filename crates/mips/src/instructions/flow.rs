@@ -1,4 +1,4 @@
-use crate::types::RegisterOrNumber;
+use crate::types::{Line, RegisterOrNumber};
 
 /// Instructions for flow control, branching, and jumping
 pub enum FlowControl {
@@ -9,7 +9,7 @@ pub enum FlowControl {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
         c: RegisterOrNumber,
-        d: RegisterOrNumber,
+        d: Line,
     },
     /// Branch to line c if a !=b and store next line number in ra
     ///
@@ -18,7 +18,7 @@ pub enum FlowControl {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
         c: RegisterOrNumber,
-        d: RegisterOrNumber,
+        d: Line,
     },
     /// Branch to line c if abs(a) <= float.epsilon * 8
     ///
@@ -26,7 +26,7 @@ pub enum FlowControl {
     BranchAbsoluteZero {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if abs(a) <= float.epsilon * 8
     ///
@@ -34,7 +34,7 @@ pub enum FlowControl {
     BranchAbsoluteZeroAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a == b
     ///
@@ -42,7 +42,7 @@ pub enum FlowControl {
     BranchEqual {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a == b and store next line number in ra
     ///
@@ -50,29 +50,23 @@ pub enum FlowControl {
     BranchEqualAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line b if a == 0
     ///
     /// beqz a(r?|num) b(r?|num)
-    BranchEqualZero {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchEqualZero { a: RegisterOrNumber, b: Line },
     /// Branch to line b if a == 0 and store next line number in ra
     ///
     /// beqzal a(r?|num) b(r?|num)
-    BranchEqualZeroAndLink {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchEqualZeroAndLink { a: RegisterOrNumber, b: Line },
     /// Branch to line c if a >= b
     ///
     /// bge a(r?|num) b(r?|num) c(r?|num)
     BranchGreaterOrEqual {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a >= b and store next line number in ra
     ///
@@ -80,29 +74,23 @@ pub enum FlowControl {
     BranchGreaterOrEqualAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line b if a >= 0
     ///
     /// bgez a(r?|num) b(r?|num)
-    BranchGreaterOrEqualZero {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchGreaterOrEqualZero { a: RegisterOrNumber, b: Line },
     /// Branch to line b if a >= 0 and store next line number in ra
     ///
     /// bgezal a(r?|num) b(r?|num)
-    BranchGreaterOrEqualZeroAndLink {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchGreaterOrEqualZeroAndLink { a: RegisterOrNumber, b: Line },
     /// Branch to line c if a > b
     ///
     /// bgt a(r?|num) b(r?|num) c(r?|num)
     BranchGreaterThan {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a > b and store next line number in ra
     ///
@@ -110,29 +98,23 @@ pub enum FlowControl {
     BranchGreaterThanAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line b if a > 0
     ///
     /// bgtz a(r?|num) b(r?|num)
-    BranchGreaterThanZero {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchGreaterThanZero { a: RegisterOrNumber, b: Line },
     /// Branch to line b if a > 0 and store next line number in ra
     ///
     /// bgtzal a(r?|num) b(r?|num)
-    BranchGreaterThanZeroAndLink {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchGreaterThanZeroAndLink { a: RegisterOrNumber, b: Line },
     /// Branch to line c if a <= b
     ///
     /// ble a(r?|num) b(r?|num) c(r?|num)
     BranchLessOrEqual {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a <= b and store next line number in ra
     ///
@@ -140,29 +122,23 @@ pub enum FlowControl {
     BranchLessOrEqualAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line b if a <= 0
     ///
     /// blez a(r?|num) b(r?|num)
-    BranchLessOrEqualZero {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchLessOrEqualZero { a: RegisterOrNumber, b: Line },
     /// Branch to line b if a <= 0 and store next line number in ra
     ///
     /// blezal a(r?|num) b(r?|num)
-    BranchLessOrEqualZeroAndLink {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchLessOrEqualZeroAndLink { a: RegisterOrNumber, b: Line },
     /// Branch to line c if a < b
     ///
     /// blt a(r?|num) b(r?|num) c(r?|num)
     BranchLessThan {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a < b and store next line number in ra
     ///
@@ -170,22 +146,16 @@ pub enum FlowControl {
     BranchLessThanAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line b if a < 0
     ///
     /// bltz a(r?|num) b(r?|num)
-    BranchLessThanZero {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchLessThanZero { a: RegisterOrNumber, b: Line },
     /// Branch to line b if a < 0 and store next line number in ra
     ///
     /// bltzal a(r?|num) b(r?|num)
-    BranchLessThanZeroAndLink {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchLessThanZeroAndLink { a: RegisterOrNumber, b: Line },
     /// Branch to line d if abs(a-b) > max(c*max(abs(a), abs(b)), float.epsilon*8)
     ///
     /// bna a(r?|num) b(r?|num) c(r?|num) d(r?|num)
@@ -193,7 +163,7 @@ pub enum FlowControl {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
         c: RegisterOrNumber,
-        d: RegisterOrNumber,
+        d: Line,
     },
     /// Branch to line d if abs(a-b) > max(c*max(abs(a), abs(b)), float.epsilon*8) and store next line number in ra
     ///
@@ -202,7 +172,7 @@ pub enum FlowControl {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
         c: RegisterOrNumber,
-        d: RegisterOrNumber,
+        d: Line,
     },
     /// Branch to line c if abs(a) > float.epsilon*8
     ///
@@ -210,7 +180,7 @@ pub enum FlowControl {
     BranchNotApproximatelyZero {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if abs(a) > float.epsilon*8 and store next line number in ra
     ///
@@ -218,7 +188,7 @@ pub enum FlowControl {
     BranchNotApproximatelyZeroAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a != b
     ///
@@ -226,7 +196,7 @@ pub enum FlowControl {
     BranchNotEqual {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line c if a != b and store next line number in ra
     ///
@@ -234,22 +204,16 @@ pub enum FlowControl {
     BranchNotEqualAndLink {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
-        c: RegisterOrNumber,
+        c: Line,
     },
     /// Branch to line b if a != 0
     ///
     /// bnez a(r?|num) b(r?|num)
-    BranchNotEqualZero {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchNotEqualZero { a: RegisterOrNumber, b: Line },
     /// Branch to line b if a != 0 and store next line number in ra
     ///
     /// bnezal a(r?|num) b(r?|num)
-    BranchNotEqualZeroAndLink {
-        a: RegisterOrNumber,
-        b: RegisterOrNumber,
-    },
+    BranchNotEqualZeroAndLink { a: RegisterOrNumber, b: Line },
     /// Relative branch to line d if abs(a-b) <= max(c*max(abs(a), abs(b)), float.epsilon*8)
     ///
     /// brap a(r?|num) b(r?|num) c(r?|num) d(r?|num)
@@ -377,15 +341,94 @@ pub enum FlowControl {
     /// Jump execution to line a
     ///
     /// j int
-    Jump { a: i32 },
+    Jump { a: Line },
     /// Jump execution to line a and store next line number in ra
     ///
     /// jal int
-    JumpAndLink { a: i32 },
+    JumpAndLink { a: Line },
     /// Relative jump execution to line a
     ///
-    /// jr int
-    JumpRelative { a: i32 },
+    /// jr a(r?|num)
+    JumpRelative { a: RegisterOrNumber },
+}
+
+/// Matches the line an absolute jump or branch goes to, by reference or mutable reference.
+macro_rules! target {
+    ($flow_control:expr) => {
+        match $flow_control {
+            FlowControl::Jump { a: line }
+            | FlowControl::JumpAndLink { a: line }
+            | FlowControl::BranchAbsoluteLessThan { d: line, .. }
+            | FlowControl::BranchAbsoluteLessThanAndLink { d: line, .. }
+            | FlowControl::BranchAbsoluteZero { c: line, .. }
+            | FlowControl::BranchAbsoluteZeroAndLink { c: line, .. }
+            | FlowControl::BranchEqual { c: line, .. }
+            | FlowControl::BranchEqualAndLink { c: line, .. }
+            | FlowControl::BranchEqualZero { b: line, .. }
+            | FlowControl::BranchEqualZeroAndLink { b: line, .. }
+            | FlowControl::BranchGreaterOrEqual { c: line, .. }
+            | FlowControl::BranchGreaterOrEqualAndLink { c: line, .. }
+            | FlowControl::BranchGreaterOrEqualZero { b: line, .. }
+            | FlowControl::BranchGreaterOrEqualZeroAndLink { b: line, .. }
+            | FlowControl::BranchGreaterThan { c: line, .. }
+            | FlowControl::BranchGreaterThanAndLink { c: line, .. }
+            | FlowControl::BranchGreaterThanZero { b: line, .. }
+            | FlowControl::BranchGreaterThanZeroAndLink { b: line, .. }
+            | FlowControl::BranchLessOrEqual { c: line, .. }
+            | FlowControl::BranchLessOrEqualAndLink { c: line, .. }
+            | FlowControl::BranchLessOrEqualZero { b: line, .. }
+            | FlowControl::BranchLessOrEqualZeroAndLink { b: line, .. }
+            | FlowControl::BranchLessThan { c: line, .. }
+            | FlowControl::BranchLessThanAndLink { c: line, .. }
+            | FlowControl::BranchLessThanZero { b: line, .. }
+            | FlowControl::BranchLessThanZeroAndLink { b: line, .. }
+            | FlowControl::BranchNotApproximatelyEqual { d: line, .. }
+            | FlowControl::BranchNotApproximatelyEqualAndLink { d: line, .. }
+            | FlowControl::BranchNotApproximatelyZero { c: line, .. }
+            | FlowControl::BranchNotApproximatelyZeroAndLink { c: line, .. }
+            | FlowControl::BranchNotEqual { c: line, .. }
+            | FlowControl::BranchNotEqualAndLink { c: line, .. }
+            | FlowControl::BranchNotEqualZero { b: line, .. }
+            | FlowControl::BranchNotEqualZeroAndLink { b: line, .. } => Some(line),
+            _ => None,
+        }
+    };
+}
+
+impl FlowControl {
+    /// The line this instruction jumps to, unless the jump is relative.
+    pub fn target(&self) -> Option<&Line> {
+        target!(self)
+    }
+
+    /// The line this instruction jumps to, unless the jump is relative.
+    pub fn target_mut(&mut self) -> Option<&mut Line> {
+        target!(self)
+    }
+
+    /// The distance from the current line that a relative jump or branch goes.
+    pub fn offset(&self) -> Option<&RegisterOrNumber> {
+        match self {
+            FlowControl::JumpRelative { a: offset }
+            | FlowControl::RelativeBranchApproximatelyEqual { d: offset, .. }
+            | FlowControl::RelativeBranchApproximatelyZero { c: offset, .. }
+            | FlowControl::RelativeBranchEqual { c: offset, .. }
+            | FlowControl::RelativeBranchEqualZero { b: offset, .. }
+            | FlowControl::RelativeBranchGreaterOrEqual { c: offset, .. }
+            | FlowControl::RelativeBranchGreaterOrEqualZero { b: offset, .. }
+            | FlowControl::RelativeBranchGreaterThan { c: offset, .. }
+            | FlowControl::RelativeBranchGreaterThanZero { b: offset, .. }
+            | FlowControl::RelativeBranchLessOrEqual { c: offset, .. }
+            | FlowControl::RelativeBranchLessOrEqualZero { b: offset, .. }
+            | FlowControl::RelativeBranchLessThan { c: offset, .. }
+            | FlowControl::RelativeBranchLessThanZero { b: offset, .. }
+            | FlowControl::RelativeBranchNotApproximatelyEqual { d: offset, .. }
+            | FlowControl::RelativeBranchNotApproximatelyZero { c: offset, .. }
+            | FlowControl::RelativeBranchNotEqual { c: offset, .. }
+            | FlowControl::RelativeBranchNotEqualZero { b: offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for FlowControl {
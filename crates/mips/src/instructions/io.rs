@@ -1,8 +1,8 @@
 use crate::{
     error::Error,
     types::{
-        BatchMode, Device, DeviceVariable, Reagent, ReagentMode, Register, RegisterOrNumber, Slot,
-        TypeHash,
+        BatchMode, Device, DeviceVariable, Line, Reagent, ReagentMode, Register, RegisterOrNumber,
+        Slot, SlotVariable, TypeHash,
     },
 };
 
@@ -11,31 +11,19 @@ pub enum DeviceIo {
     /// branch to line a if device d isn't set
     ///
     /// bdns d? a(r?|num)
-    BranchDeviceNotSet {
-        device: Device,
-        line: RegisterOrNumber,
-    },
+    BranchDeviceNotSet { device: Device, line: Line },
     /// Jump execution to line a and store next line number if device is not set
     ///
     /// bdnsal d? a(r?|num)
-    BranchDeviceNotSetAndLink {
-        device: Device,
-        line: RegisterOrNumber,
-    },
+    BranchDeviceNotSetAndLink { device: Device, line: Line },
     /// Branch to line a if device d is set
     ///
     /// bdse d? a(r?|num)
-    BranchDeviceSet {
-        device: Device,
-        line: RegisterOrNumber,
-    },
+    BranchDeviceSet { device: Device, line: Line },
     /// Jump execution to line a and store next line number if device is set
     ///
     /// bdseal d? a(r?|num)
-    BranchDeviceSetAndLink {
-        device: Device,
-        line: RegisterOrNumber,
-    },
+    BranchDeviceSetAndLink { device: Device, line: Line },
     /// Relative jump to line a if device is not set
     ///
     /// brdns d? a(r?|num)
@@ -81,12 +69,12 @@ pub enum DeviceIo {
     },
     /// Loads slot var on device to register
     ///
-    /// ls r? d? int var
+    /// ls r? d? slotIndex var
     LoadSlot {
         register: Register,
         device: Device,
         slot: Slot,
-        variable: DeviceVariable,
+        variable: SlotVariable,
     },
     /// Stores register to var on device
     ///
@@ -104,6 +92,49 @@ pub enum DeviceIo {
         variable: DeviceVariable,
         register: Register,
     },
+    /// Stores register to slot var on device
+    ///
+    /// ss d? slotIndex var r?
+    StoreSlot {
+        device: Device,
+        slot: Slot,
+        variable: SlotVariable,
+        register: Register,
+    },
+}
+
+/// Matches the line an absolute branch goes to, by reference or mutable reference.
+macro_rules! target {
+    ($device_io:expr) => {
+        match $device_io {
+            DeviceIo::BranchDeviceNotSet { line, .. }
+            | DeviceIo::BranchDeviceNotSetAndLink { line, .. }
+            | DeviceIo::BranchDeviceSet { line, .. }
+            | DeviceIo::BranchDeviceSetAndLink { line, .. } => Some(line),
+            _ => None,
+        }
+    };
+}
+
+impl DeviceIo {
+    /// The line this instruction branches to, unless the branch is relative.
+    pub fn target(&self) -> Option<&Line> {
+        target!(self)
+    }
+
+    /// The line this instruction branches to, unless the branch is relative.
+    pub fn target_mut(&mut self) -> Option<&mut Line> {
+        target!(self)
+    }
+
+    /// The distance from the current line that a relative branch goes.
+    pub fn offset(&self) -> Option<&RegisterOrNumber> {
+        match self {
+            DeviceIo::BranchRelativeDeviceNotSet { line, .. }
+            | DeviceIo::BranchRelativeDeviceSet { line, .. } => Some(line),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for DeviceIo {
@@ -162,34 +193,16 @@ impl std::fmt::Display for DeviceIo {
                 variable,
                 register,
             } => write!(f, "sb {} {} {}", type_hash, variable, register),
+            DeviceIo::StoreSlot {
+                device,
+                slot,
+                variable,
+                register,
+            } => write!(f, "ss {} {} {} {}", device, slot, variable, register),
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        instructions::{DeviceIo, Instruction},
-        types::{Device, Number, RegisterOrNumber},
-    };
-
-    #[test]
-    fn serde_device_io_bdns() {
-        let instruction = Instruction::DeviceIo(DeviceIo::BranchDeviceNotSet {
-            device: Device::D0,
-            line: RegisterOrNumber::Number(Number::Int(5)),
-        });
-
-        let instruction_str = format!("{}", instruction);
-        println!("{}", instruction_str);
-
-        assert_eq!(
-            instruction_str, "bdns d0 5",
-            "Instruction string does not match expected"
-        );
-    }
-}
-
 // DeviceIo
 impl std::str::FromStr for DeviceIo {
     type Err = Error;
@@ -298,3 +311,27 @@ impl std::str::FromStr for DeviceIo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        instructions::{DeviceIo, Instruction},
+        types::Device,
+    };
+
+    #[test]
+    fn serde_device_io_bdns() {
+        let instruction = Instruction::DeviceIo(DeviceIo::BranchDeviceNotSet {
+            device: Device::D0,
+            line: 5.into(),
+        });
+
+        let instruction_str = format!("{}", instruction);
+        println!("{}", instruction_str);
+
+        assert_eq!(
+            instruction_str, "bdns d0 5",
+            "Instruction string does not match expected"
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use crate::types::{Register, RegisterOrNumber};
 
-/// Boolean logic instructions.
+/// Bitwise logic instructions.
+/// The operands are treated as 64 bit integers.
 pub enum Logic {
-    /// Register = 1 if a != 0 and b != 0 else 0
+    /// Register = a & b
     ///
     /// and r? a(r?|num) b(r?|num)
     And {
@@ -10,7 +11,7 @@ pub enum Logic {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
     },
-    /// Register = 1 if a == 0 and b == 0 else 0
+    /// Register = ~(a | b)
     ///
     /// nor r? a(r?|num) b(r?|num)
     Nor {
@@ -18,7 +19,7 @@ pub enum Logic {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
     },
-    /// Register = 1 if a and/or b != 0 else 0
+    /// Register = a | b
     ///
     /// or r? a(r?|num) b(r?|num)
     Or {
@@ -26,7 +27,7 @@ pub enum Logic {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
     },
-    /// Register = 1 if either a or b is nonzero, otherwise 0
+    /// Register = a ^ b
     ///
     /// xor r? a(r?|num) b(r?|num)
     Xor {
@@ -34,6 +35,38 @@ pub enum Logic {
         a: RegisterOrNumber,
         b: RegisterOrNumber,
     },
+    /// Register = a << b, shifting in zeros
+    ///
+    /// sll r? a(r?|num) b(r?|num)
+    ShiftLeftLogical {
+        register: Register,
+        a: RegisterOrNumber,
+        b: RegisterOrNumber,
+    },
+    /// Register = a << b, shifting in zeros
+    ///
+    /// sla r? a(r?|num) b(r?|num)
+    ShiftLeftArithmetic {
+        register: Register,
+        a: RegisterOrNumber,
+        b: RegisterOrNumber,
+    },
+    /// Register = a >> b, shifting in zeros
+    ///
+    /// srl r? a(r?|num) b(r?|num)
+    ShiftRightLogical {
+        register: Register,
+        a: RegisterOrNumber,
+        b: RegisterOrNumber,
+    },
+    /// Register = a >> b, preserving the sign
+    ///
+    /// sra r? a(r?|num) b(r?|num)
+    ShiftRightArithmetic {
+        register: Register,
+        a: RegisterOrNumber,
+        b: RegisterOrNumber,
+    },
 }
 
 impl std::fmt::Display for Logic {
@@ -43,6 +76,10 @@ impl std::fmt::Display for Logic {
             Logic::Nor { register, a, b } => write!(f, "nor {register} {a} {b}"),
             Logic::Or { register, a, b } => write!(f, "or {register} {a} {b}"),
             Logic::Xor { register, a, b } => write!(f, "xor {register} {a} {b}"),
+            Logic::ShiftLeftLogical { register, a, b } => write!(f, "sll {register} {a} {b}"),
+            Logic::ShiftLeftArithmetic { register, a, b } => write!(f, "sla {register} {a} {b}"),
+            Logic::ShiftRightLogical { register, a, b } => write!(f, "srl {register} {a} {b}"),
+            Logic::ShiftRightArithmetic { register, a, b } => write!(f, "sra {register} {a} {b}"),
         }
     }
 }
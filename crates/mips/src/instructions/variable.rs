@@ -1,4 +1,4 @@
-use crate::types::{Register, RegisterOrNumber};
+use crate::types::{Device, Register, RegisterOrNumber};
 
 /// Instructions for variable selection
 pub enum VariableSelection {
@@ -22,17 +22,11 @@ pub enum VariableSelection {
     /// Register = 1 if device is not set else 0
     ///
     /// sdns r? d?
-    SelectDeviceNotSet {
-        register: Register,
-        d: RegisterOrNumber,
-    },
+    SelectDeviceNotSet { register: Register, device: Device },
     /// Register = 1 if device is set else 0
     ///
     /// sdse r? d?
-    SelectDeviceSet {
-        register: Register,
-        d: RegisterOrNumber,
-    },
+    SelectDeviceSet { register: Register, device: Device },
     /// Register = b if a != 0 else c
     ///
     /// select r? a(r?|num) b(r?|num) c(r?|num)
@@ -160,11 +154,11 @@ impl std::fmt::Display for VariableSelection {
             VariableSelection::SelectApproximatelyZero { register, a, b } => {
                 write!(f, "sapz {register} {a} {b}")
             }
-            VariableSelection::SelectDeviceNotSet { register, d } => {
-                write!(f, "sdns {register} {d}")
+            VariableSelection::SelectDeviceNotSet { register, device } => {
+                write!(f, "sdns {register} {device}")
             }
-            VariableSelection::SelectDeviceSet { register, d } => {
-                write!(f, "sdse {register} {d}")
+            VariableSelection::SelectDeviceSet { register, device } => {
+                write!(f, "sdse {register} {device}")
             }
             VariableSelection::Select { register, a, b, c } => {
                 write!(f, "select {register} {a} {b} {c}")
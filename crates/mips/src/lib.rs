@@ -6,5 +6,6 @@
 /// This is a collection of enums and structs that represent Stationeers MIPS instructions.
 /// Each type implments the `Display` trait, so you can print them to a string.
 pub mod error;
+pub mod hash;
 pub mod instructions;
 pub mod types;
@@ -1,7 +1,7 @@
 use crate::error::Error;
 
 #[repr(u8)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Device {
     D0,
     D1,
@@ -44,7 +44,7 @@ impl std::str::FromStr for Device {
 }
 
 #[repr(u8)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Register {
     R0,
     R1,
@@ -146,7 +146,7 @@ impl From<u8> for Register {
 #[derive(Clone, Debug)]
 pub enum Number {
     Int(i32),
-    Float(f32),
+    Float(f64),
 }
 
 impl std::fmt::Display for Number {
@@ -164,7 +164,7 @@ impl std::str::FromStr for Number {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if let Ok(int) = s.parse::<i32>() {
             Ok(Number::Int(int))
-        } else if let Ok(float) = s.parse::<f32>() {
+        } else if let Ok(float) = s.parse::<f64>() {
             Ok(Number::Float(float))
         } else {
             Err(Error::ParseError(s.to_string()))
@@ -213,6 +213,50 @@ impl From<Number> for RegisterOrNumber {
     }
 }
 
+/// The line a jump or branch goes to.
+#[derive(Clone, Debug)]
+pub enum Line {
+    /// A line number, or a register holding one such as `ra`
+    Address(RegisterOrNumber),
+    /// The line of a `label:`, which is resolved by the game or by relocating the program
+    Label(String),
+}
+
+impl std::fmt::Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Line::Address(address) => write!(f, "{}", address),
+            Line::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+impl std::str::FromStr for Line {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(address) = s.parse::<RegisterOrNumber>() {
+            Ok(Line::Address(address))
+        } else if s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            Ok(Line::Label(s.to_string()))
+        } else {
+            Err(Error::ParseError(s.to_string()))
+        }
+    }
+}
+
+impl From<i32> for Line {
+    fn from(line: i32) -> Self {
+        Line::Address(Number::Int(line).into())
+    }
+}
+
+impl From<Register> for Line {
+    fn from(register: Register) -> Self {
+        Line::Address(register.into())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum DeviceVariable {
     Activate,
@@ -504,8 +548,9 @@ impl std::str::FromStr for Reagent {
     }
 }
 
+/// The index of a slot of a device.
 #[derive(Clone, Debug)]
-pub struct Slot(u8);
+pub struct Slot(RegisterOrNumber);
 
 impl std::fmt::Display for Slot {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -517,9 +562,113 @@ impl std::str::FromStr for Slot {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.parse::<u8>() {
-            Ok(v) => Ok(Slot(v)),
-            Err(_) => Err(Error::ParseError(s.to_string())),
+        Ok(Slot(s.parse()?))
+    }
+}
+
+impl From<RegisterOrNumber> for Slot {
+    fn from(index: RegisterOrNumber) -> Self {
+        Slot(index)
+    }
+}
+
+/// A logic type of the contents of a slot.
+#[derive(Clone, Debug)]
+pub enum SlotVariable {
+    Charge,
+    ChargeRatio,
+    Class,
+    Damage,
+    Efficiency,
+    Growth,
+    Health,
+    Lock,
+    Mature,
+    MaxQuantity,
+    Occupied,
+    OccupantHash,
+    On,
+    Open,
+    PrefabHash,
+    Pressure,
+    PressureAir,
+    PressureWaste,
+    Quantity,
+    ReferenceId,
+    Seeding,
+    SortingClass,
+    Temperature,
+}
+
+impl SlotVariable {
+    /// Whether the variable can be written using `ss`.
+    pub fn is_writable(&self) -> bool {
+        matches!(
+            self,
+            SlotVariable::Lock | SlotVariable::On | SlotVariable::Open
+        )
+    }
+}
+
+impl std::str::FromStr for SlotVariable {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Charge" => Ok(SlotVariable::Charge),
+            "ChargeRatio" => Ok(SlotVariable::ChargeRatio),
+            "Class" => Ok(SlotVariable::Class),
+            "Damage" => Ok(SlotVariable::Damage),
+            "Efficiency" => Ok(SlotVariable::Efficiency),
+            "Growth" => Ok(SlotVariable::Growth),
+            "Health" => Ok(SlotVariable::Health),
+            "Lock" => Ok(SlotVariable::Lock),
+            "Mature" => Ok(SlotVariable::Mature),
+            "MaxQuantity" => Ok(SlotVariable::MaxQuantity),
+            "Occupied" => Ok(SlotVariable::Occupied),
+            "OccupantHash" => Ok(SlotVariable::OccupantHash),
+            "On" => Ok(SlotVariable::On),
+            "Open" => Ok(SlotVariable::Open),
+            "PrefabHash" => Ok(SlotVariable::PrefabHash),
+            "Pressure" => Ok(SlotVariable::Pressure),
+            "PressureAir" => Ok(SlotVariable::PressureAir),
+            "PressureWaste" => Ok(SlotVariable::PressureWaste),
+            "Quantity" => Ok(SlotVariable::Quantity),
+            "ReferenceId" => Ok(SlotVariable::ReferenceId),
+            "Seeding" => Ok(SlotVariable::Seeding),
+            "SortingClass" => Ok(SlotVariable::SortingClass),
+            "Temperature" => Ok(SlotVariable::Temperature),
+            _ => Err(Error::ParseError(s.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for SlotVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SlotVariable::Charge => write!(f, "Charge"),
+            SlotVariable::ChargeRatio => write!(f, "ChargeRatio"),
+            SlotVariable::Class => write!(f, "Class"),
+            SlotVariable::Damage => write!(f, "Damage"),
+            SlotVariable::Efficiency => write!(f, "Efficiency"),
+            SlotVariable::Growth => write!(f, "Growth"),
+            SlotVariable::Health => write!(f, "Health"),
+            SlotVariable::Lock => write!(f, "Lock"),
+            SlotVariable::Mature => write!(f, "Mature"),
+            SlotVariable::MaxQuantity => write!(f, "MaxQuantity"),
+            SlotVariable::Occupied => write!(f, "Occupied"),
+            SlotVariable::OccupantHash => write!(f, "OccupantHash"),
+            SlotVariable::On => write!(f, "On"),
+            SlotVariable::Open => write!(f, "Open"),
+            SlotVariable::PrefabHash => write!(f, "PrefabHash"),
+            SlotVariable::Pressure => write!(f, "Pressure"),
+            SlotVariable::PressureAir => write!(f, "PressureAir"),
+            SlotVariable::PressureWaste => write!(f, "PressureWaste"),
+            SlotVariable::Quantity => write!(f, "Quantity"),
+            SlotVariable::ReferenceId => write!(f, "ReferenceId"),
+            SlotVariable::Seeding => write!(f, "Seeding"),
+            SlotVariable::SortingClass => write!(f, "SortingClass"),
+            SlotVariable::Temperature => write!(f, "Temperature"),
         }
     }
 }
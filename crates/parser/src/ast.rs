@@ -15,19 +15,29 @@ pub enum Statement {
         identifier: Identifier,
         expression: Box<Expr>,
     },
+    /// Applies an operator to a variable in place, e.g. `x += 1;` or `x++;`
+    CompoundAssignment {
+        identifier: Identifier,
+        operator: BinaryOpcode,
+        expression: Box<Expr>,
+    },
     Definition {
         identifier: Identifier,
         expression: Box<Expr>,
     },
     Alias {
+        /// The `///` comments written before the alias
+        docs: Vec<String>,
         /// The identifier to alias to
         identifier: Identifier,
         /// The new alias to the identifier
         alias: Identifier,
     },
     /// Defines a constant value for use in expressions
-    Constant(Identifier, Value),
+    Constant(Identifier, Box<Expr>),
     Function {
+        /// The `///` comments written before the function
+        docs: Vec<String>,
         identifier: Identifier,
         parameters: Vec<Identifier>,
         body: Block,
@@ -38,11 +48,40 @@ pub enum Statement {
     },
     Block(Block),
     Loop {
+        /// The label used to refer to the loop from `break` and `continue`
+        label: Option<Identifier>,
+        body: Block,
+    },
+    While {
+        /// The label used to refer to the loop from `break` and `continue`
+        label: Option<Identifier>,
+        condition: Box<Expr>,
+        body: Block,
+    },
+    /// Counts a variable over a range of integers
+    For {
+        /// The label used to refer to the loop from `break` and `continue`
+        label: Option<Identifier>,
+        variable: Identifier,
+        range: Range,
         body: Block,
     },
+    /// Exits the innermost loop, or the loop with the given label
+    Break(Option<Identifier>),
+    /// Skips to the next iteration of the innermost loop, or the loop with the given label
+    Continue(Option<Identifier>),
     IfStatement(IfStatement),
+    /// Runs the first arm whose pattern matches the value of the expression
+    Match {
+        expression: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
     DeviceStatement(DeviceStatement),
     Yield,
+    /// Returns from the current function, optionally with a value
+    Return(Option<Box<Expr>>),
+    /// Halts the program unless all of the given devices are connected
+    Require(Vec<Identifier>),
 }
 
 impl Statement {
@@ -53,6 +92,18 @@ impl Statement {
         }
     }
 
+    pub fn new_compound_assignment(
+        identifier: Identifier,
+        operator: BinaryOpcode,
+        expression: Box<Expr>,
+    ) -> Self {
+        Self::CompoundAssignment {
+            identifier,
+            operator,
+            expression,
+        }
+    }
+
     pub fn new_definition(identifier: Identifier, expression: Box<Expr>) -> Self {
         Self::Definition {
             identifier,
@@ -60,16 +111,26 @@ impl Statement {
         }
     }
 
-    pub fn new_alias(identifier: Identifier, alias: Identifier) -> Self {
-        Self::Alias { identifier, alias }
+    pub fn new_alias(docs: Vec<String>, identifier: Identifier, alias: Identifier) -> Self {
+        Self::Alias {
+            docs,
+            identifier,
+            alias,
+        }
     }
 
-    pub fn new_constant(identifier: Identifier, value: Value) -> Self {
+    pub fn new_constant(identifier: Identifier, value: Box<Expr>) -> Self {
         Self::Constant(identifier, value)
     }
 
-    pub fn new_function(identifier: Identifier, parameters: Vec<Identifier>, body: Block) -> Self {
+    pub fn new_function(
+        docs: Vec<String>,
+        identifier: Identifier,
+        parameters: Vec<Identifier>,
+        body: Block,
+    ) -> Self {
         Self::Function {
+            docs,
             identifier,
             parameters,
             body,
@@ -87,14 +148,48 @@ impl Statement {
         Self::Block(block)
     }
 
-    pub fn new_loop(body: Block) -> Self {
-        Self::Loop { body }
+    pub fn new_loop(label: Option<Identifier>, body: Block) -> Self {
+        Self::Loop { label, body }
+    }
+
+    pub fn new_while(label: Option<Identifier>, condition: Box<Expr>, body: Block) -> Self {
+        Self::While {
+            label,
+            condition,
+            body,
+        }
+    }
+
+    pub fn new_for(
+        label: Option<Identifier>,
+        variable: Identifier,
+        range: Range,
+        body: Block,
+    ) -> Self {
+        Self::For {
+            label,
+            variable,
+            range,
+            body,
+        }
+    }
+
+    pub fn new_break(label: Option<Identifier>) -> Self {
+        Self::Break(label)
+    }
+
+    pub fn new_continue(label: Option<Identifier>) -> Self {
+        Self::Continue(label)
     }
 
     pub fn new_if(if_statement: IfStatement) -> Self {
         Self::IfStatement(if_statement)
     }
 
+    pub fn new_match(expression: Box<Expr>, arms: Vec<MatchArm>) -> Self {
+        Self::Match { expression, arms }
+    }
+
     pub fn new_device(statement: DeviceStatement) -> Self {
         Self::DeviceStatement(statement)
     }
@@ -102,6 +197,14 @@ impl Statement {
     pub fn new_yield() -> Self {
         Self::Yield
     }
+
+    pub fn new_return(value: Option<Box<Expr>>) -> Self {
+        Self::Return(value)
+    }
+
+    pub fn new_require(devices: Vec<Identifier>) -> Self {
+        Self::Require(devices)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -110,6 +213,24 @@ pub enum Expr {
     Identifier(Identifier),
     BinaryOp(Box<Expr>, BinaryOpcode, Box<Expr>),
     UnaryOp(UnaryOpcode, Box<Expr>),
+    /// Calls a function and evaluates to its return value
+    Call(Identifier, Vec<Box<Expr>>),
+    /// Reads a variable of a device, e.g. `Sensor.Temperature`
+    DeviceRead(Identifier, Identifier),
+    /// Reads a variable of all devices of a type and combines the values using a batch mode,
+    /// e.g. `batch(StructureGasSensor).Temperature.Average`
+    BatchRead(Box<Expr>, Identifier, Identifier),
+    /// Reads a variable of the contents of a slot of a device, e.g. `Sorter.slot[0].OccupantHash`
+    SlotRead(Identifier, Box<Expr>, Identifier),
+    /// Reads a reagent of a device, e.g. `Furnace.reagent(Contents, Iron)`
+    ReagentRead(Identifier, Identifier, Identifier),
+    /// Evaluates to the second expression if the condition is true and to the third otherwise,
+    /// e.g. `if a < b { a } else { b }`. Branches without side effects may both be evaluated.
+    Select(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// Whether a device is connected to a pin, e.g. `connected(Sensor)`
+    Connected(Identifier),
+    /// The hash of a name, e.g. `HASH("StructureGasSensor")`, evaluated at compile time
+    Hash(String),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -126,11 +247,28 @@ pub enum BinaryOpcode {
     GreaterEquals,
     Lower,
     LowerEquals,
+    /// The remainder of a division, `%`
+    Rem,
+    /// Exponentiation, `**`
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    /// `<<`
+    ShiftLeft,
+    /// `>>`, preserving the sign
+    ShiftRight,
+    /// `>>>`, shifting in zeros
+    ShiftRightLogical,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum UnaryOpcode {
     Not,
+    /// Negation, `-`
+    Neg,
+    /// Bitwise not, `~`
+    BitNot,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -138,6 +276,76 @@ pub enum Value {
     Integer(i64),
     Float(f64),
     Boolean(bool),
+    /// A number with a unit, e.g. `20C`. The value is given in the unit as written, use
+    /// `Unit::normalize` to convert it to the units the game uses.
+    Quantity(f64, Unit),
+}
+
+/// The units that numeric literals can be written in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    Pascal,
+    KiloPascal,
+    MegaPascal,
+}
+
+/// The physical quantity that a unit measures.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dimension {
+    Temperature,
+    Pressure,
+}
+
+impl Unit {
+    /// Converts a value in this unit into the unit the game uses for its dimension, which is
+    /// Kelvin for temperatures and kPa for pressures.
+    /// Temperatures are offset in hundredths of a Kelvin, as 273.15 has no exact binary form and
+    /// adding it directly would turn e.g. `-20C` into 253.14999999999998.
+    pub fn normalize(self, value: f64) -> f64 {
+        match self {
+            Unit::Celsius => (value * 100.0 + 27315.0) / 100.0,
+            Unit::Fahrenheit => ((value - 32.0) * 500.0 / 9.0 + 27315.0) / 100.0,
+            Unit::Kelvin | Unit::KiloPascal => value,
+            Unit::Pascal => value / 1000.0,
+            Unit::MegaPascal => value * 1000.0,
+        }
+    }
+
+    pub fn dimension(self) -> Dimension {
+        match self {
+            Unit::Celsius | Unit::Fahrenheit | Unit::Kelvin => Dimension::Temperature,
+            Unit::Pascal | Unit::KiloPascal | Unit::MegaPascal => Dimension::Pressure,
+        }
+    }
+}
+
+impl Dimension {
+    /// The unit the game uses for the dimension.
+    pub fn base_unit(self) -> Unit {
+        match self {
+            Dimension::Temperature => Unit::Kelvin,
+            Dimension::Pressure => Unit::KiloPascal,
+        }
+    }
+}
+
+impl std::str::FromStr for Unit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "C" => Ok(Unit::Celsius),
+            "F" => Ok(Unit::Fahrenheit),
+            "K" => Ok(Unit::Kelvin),
+            "Pa" => Ok(Unit::Pascal),
+            "kPa" => Ok(Unit::KiloPascal),
+            "MPa" => Ok(Unit::MegaPascal),
+            _ => Err(()),
+        }
+    }
 }
 
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
@@ -161,9 +369,9 @@ impl From<Identifier> for String {
     }
 }
 
-impl ToString for Identifier {
-    fn to_string(&self) -> String {
-        self.0.clone()
+impl std::fmt::Display for Identifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -219,6 +427,68 @@ impl IfStatement {
             else_body,
         }
     }
+
+    /// Creates an `if ... else if ...` chain.
+    /// The following `if` statement becomes the only statement of the else block.
+    pub fn new_else_if(condition: Box<Expr>, body: Block, else_if: IfStatement) -> Self {
+        Self::IfElse {
+            condition,
+            body,
+            else_body: Block::Statements(vec![Statement::IfStatement(else_if)]),
+        }
+    }
+}
+
+/// A range of integers that a `for` loop counts over, e.g. `0..10` or `(1..=9).step(2)`
+#[derive(Clone, Debug)]
+pub struct Range {
+    pub start: Box<Expr>,
+    pub end: Box<Expr>,
+    /// Whether `end` is part of the range
+    pub inclusive: bool,
+    /// The amount the variable changes by in each iteration, 1 if not given
+    pub step: Option<Box<Expr>>,
+}
+
+impl Range {
+    pub fn new(start: Box<Expr>, end: Box<Expr>, inclusive: bool) -> Self {
+        Self {
+            start,
+            end,
+            inclusive,
+            step: None,
+        }
+    }
+
+    pub fn with_step(self, step: Box<Expr>) -> Self {
+        Self {
+            step: Some(step),
+            ..self
+        }
+    }
+}
+
+/// A single arm of a `match` statement
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    pub body: Block,
+}
+
+impl MatchArm {
+    pub fn new(pattern: MatchPattern, body: Block) -> Self {
+        Self { pattern, body }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum MatchPattern {
+    /// Matches a specific integer
+    Integer(i64),
+    /// Matches the value of a constant
+    Constant(Identifier),
+    /// Matches any value, written as `_`
+    Wildcard,
 }
 
 /// A statement that interacts with a device
@@ -240,6 +510,24 @@ pub enum DeviceStatement {
         /// The attribute to write to the device
         device_variable: Identifier,
     },
+    BatchWrite {
+        /// The value to write to the devices
+        value: Box<Expr>,
+        /// The type of the devices to write to
+        device_type: Box<Expr>,
+        /// The attribute to write to the devices
+        device_variable: Identifier,
+    },
+    SlotWrite {
+        /// The value to write to the slot
+        value: Box<Expr>,
+        /// The device that the slot belongs to
+        device: Identifier,
+        /// The index of the slot
+        slot: Box<Expr>,
+        /// The attribute to write to the slot
+        slot_variable: Identifier,
+    },
 }
 
 impl DeviceStatement {
@@ -258,4 +546,46 @@ impl DeviceStatement {
             device_variable,
         }
     }
+
+    /// Writes the result of applying an operator to the current value of a device variable, e.g.
+    /// `Heater.Setting += 5`.
+    pub fn new_compound_write(
+        device: Identifier,
+        device_variable: Identifier,
+        operator: BinaryOpcode,
+        value: Box<Expr>,
+    ) -> Self {
+        let current = Box::new(Expr::DeviceRead(device.clone(), device_variable.clone()));
+        Self::new_write(
+            Box::new(Expr::BinaryOp(current, operator, value)),
+            device,
+            device_variable,
+        )
+    }
+
+    pub fn new_slot_write(
+        value: Box<Expr>,
+        device: Identifier,
+        slot: Box<Expr>,
+        slot_variable: Identifier,
+    ) -> Self {
+        Self::SlotWrite {
+            value,
+            device,
+            slot,
+            slot_variable,
+        }
+    }
+
+    pub fn new_batch_write(
+        value: Box<Expr>,
+        device_type: Box<Expr>,
+        device_variable: Identifier,
+    ) -> Self {
+        Self::BatchWrite {
+            value,
+            device_type,
+            device_variable,
+        }
+    }
 }
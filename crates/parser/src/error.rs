@@ -1,7 +1,13 @@
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum Error {
     #[error("todo")]
     Todo,
+    #[error("number literal `{0}` is out of range")]
+    NumberOutOfRange(String),
+    #[error("unknown unit in `{0}`")]
+    UnknownUnit(String),
+    #[error("expected `step`, found `{0}`")]
+    ExpectedStep(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
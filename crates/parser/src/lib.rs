@@ -8,3 +8,282 @@ lalrpop_mod!(
     #[allow(clippy::all)]
     pub grammar
 );
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{Block, DeviceStatement, Expr, Statement, UnaryOpcode, Unit, Value},
+        grammar::ProgramParser,
+    };
+
+    fn parse(source: &str) -> Vec<Statement> {
+        ProgramParser::new()
+            .parse(source)
+            .unwrap_or_else(|error| panic!("{source} does not parse: {error}"))
+            .statements
+    }
+
+    /// Parses the body of `main`.
+    fn parse_body(body: &str) -> Vec<Statement> {
+        let mut statements = parse(&format!("fn main() {{ {body} }}"));
+        match statements.pop() {
+            Some(Statement::Function {
+                body: Block::Statements(statements),
+                ..
+            }) => statements,
+            statement => panic!("expected a function, found {statement:?}"),
+        }
+    }
+
+    #[test]
+    fn test_loops() {
+        let statements = parse_body(
+            "'outer: loop { while x < 10 { break 'outer; } continue; } while true { break; }",
+        );
+        let [Statement::Loop {
+            label: Some(label),
+            body: Block::Statements(body),
+        }, Statement::While {
+            label: None,
+            condition,
+            ..
+        }] = statements.as_slice()
+        else {
+            panic!("unexpected statements: {statements:?}");
+        };
+        assert_eq!(label.to_string(), "outer");
+        assert!(matches!(**condition, Expr::Constant(_)));
+
+        let [Statement::While {
+            body: Block::Statements(inner),
+            ..
+        }, Statement::Continue(None)] = body.as_slice()
+        else {
+            panic!("unexpected loop body: {body:?}");
+        };
+        assert!(
+            matches!(inner.as_slice(), [Statement::Break(Some(label))] if label.to_string() == "outer")
+        );
+
+        // a label must be followed by a loop
+        assert!(ProgramParser::new()
+            .parse("fn main() { 'outer: x = 1; }")
+            .is_err());
+    }
+
+    /// Parses an expression and writes it with every operation in parentheses, e.g.
+    /// `(a Add (b Mul c))`.
+    fn grouped(expression: &str) -> String {
+        fn group(expr: &Expr) -> String {
+            match expr {
+                Expr::Constant(Value::Integer(i)) => i.to_string(),
+                Expr::Identifier(identifier) => identifier.to_string(),
+                Expr::BinaryOp(a, op, b) => format!("({} {op:?} {})", group(a), group(b)),
+                Expr::UnaryOp(op, a) => format!("({op:?} {})", group(a)),
+                expr => panic!("unexpected expression {expr:?}"),
+            }
+        }
+
+        match parse_body(&format!("x = {expression};")).as_slice() {
+            [Statement::Assignment { expression, .. }] => group(expression),
+            statements => panic!("unexpected statements: {statements:?}"),
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        for (expression, expected) in [
+            ("a + b * c", "(a Add (b Mul c))"),
+            ("a % b * c", "((a Rem b) Mul c)"),
+            ("a - b % c", "(a Sub (b Rem c))"),
+            ("a << b + c", "(a ShiftLeft (b Add c))"),
+            ("a >> b >>> c", "((a ShiftRight b) ShiftRightLogical c)"),
+            ("a & b << c", "(a BitAnd (b ShiftLeft c))"),
+            ("a | b ^ c & d", "(a BitOr (b BitXor (c BitAnd d)))"),
+            ("a | b == c", "((a BitOr b) Equals c)"),
+            ("a == b && c || d", "(((a Equals b) Conj c) Disj d)"),
+            ("~a & b", "((BitNot a) BitAnd b)"),
+            ("-a % b", "((Neg a) Rem b)"),
+            // exponentiation binds tighter than a sign and groups from the right
+            ("-a ** b", "(Neg (a Pow b))"),
+            ("a ** -b", "(a Pow (Neg b))"),
+            ("a ** b ** c", "(a Pow (b Pow c))"),
+            ("2 * a ** 2", "(2 Mul (a Pow 2))"),
+        ] {
+            assert_eq!(grouped(expression), expected, "{expression}");
+        }
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let statements = parse_body(
+            "for i in 0..n {} 'slots: for i in (a..=b).step(k) {} for step in (0..10).step(-2) {}",
+        );
+        let [Statement::For {
+            label: None,
+            variable,
+            range: plain,
+            ..
+        }, Statement::For {
+            label: Some(label),
+            range: stepped,
+            ..
+        }, Statement::For {
+            variable: step_name,
+            range: negative,
+            ..
+        }] = statements.as_slice()
+        else {
+            panic!("unexpected statements: {statements:?}");
+        };
+        assert_eq!(variable.to_string(), "i");
+        assert!(!plain.inclusive && plain.step.is_none());
+        assert!(matches!(*plain.end, Expr::Identifier(_)));
+        assert_eq!(label.to_string(), "slots");
+        assert!(stepped.inclusive);
+        assert!(matches!(stepped.step.as_deref(), Some(Expr::Identifier(_))));
+        // `step` is only special after a range
+        assert_eq!(step_name.to_string(), "step");
+        assert!(matches!(negative.step.as_deref(), Some(Expr::UnaryOp(..))));
+
+        for source in [
+            "fn main() { for i in (0..10).skip(2) {} }",
+            "fn main() { for i in 0..10.step(2) {} }",
+        ] {
+            assert!(ProgramParser::new().parse(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_unit_literals() {
+        let statements = parse_body("x = 20C; x = 101.325kPa; x = 1_000Pa; x = -20C;");
+        let values: Vec<&Expr> = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Assignment { expression, .. } => expression.as_ref(),
+                statement => panic!("unexpected statement {statement:?}"),
+            })
+            .collect();
+        let quantity = |expr: &Expr| match expr {
+            Expr::Constant(Value::Quantity(value, unit)) => Some((*value, *unit)),
+            _ => None,
+        };
+        assert_eq!(quantity(values[0]), Some((20.0, Unit::Celsius)));
+        assert_eq!(quantity(values[1]), Some((101.325, Unit::KiloPascal)));
+        assert_eq!(quantity(values[2]), Some((1000.0, Unit::Pascal)));
+        // the sign is not part of the literal
+        match values[3] {
+            Expr::UnaryOp(UnaryOpcode::Neg, negative) => {
+                assert_eq!(quantity(negative), Some((20.0, Unit::Celsius)))
+            }
+            value => panic!("unexpected value {value:?}"),
+        }
+
+        // the unit must follow the number directly
+        assert!(ProgramParser::new()
+            .parse("fn main() { x = 5 K; }")
+            .is_err());
+    }
+
+    #[test]
+    fn test_comments() {
+        let statements = parse(
+            "// a comment
+            /* a block
+               comment */
+            /// The sensor
+            /// in the room
+            def d0 as Sensor;
+            /// not attached to anything
+            const Limit = 10;
+            /// The entry point
+            fn main() {
+                /// inside a block
+                let x = 1; // after a statement
+                x = /* inside an expression */ 2;
+                /// at the end of a block
+            }
+            /// at the end of the file",
+        );
+        let [Statement::Alias {
+            docs: alias_docs, ..
+        }, Statement::Constant(..), Statement::Function {
+            docs: function_docs,
+            body: Block::Statements(body),
+            ..
+        }] = statements.as_slice()
+        else {
+            panic!("unexpected statements: {statements:?}");
+        };
+        assert_eq!(alias_docs, &["The sensor", "in the room"]);
+        assert_eq!(function_docs, &["The entry point"]);
+        assert!(matches!(
+            body.as_slice(),
+            [Statement::Definition { .. }, Statement::Assignment { .. }]
+        ));
+
+        // only the comments right before a function document it
+        let statements = parse("/// not the docs of f\nlet x = 1;\nfn f() {}");
+        assert!(matches!(
+            statements.as_slice(),
+            [Statement::Definition { .. }, Statement::Function { docs, .. }] if docs.is_empty()
+        ));
+
+        // `////` is an ordinary comment, and a block that only holds a doc comment is empty
+        let statements = parse_body("//// four slashes\n{ /// nothing here\n}");
+        assert!(matches!(
+            statements.as_slice(),
+            [Statement::Block(Block::Statements(inner))] if inner.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_identifiers_and_device_pins() {
+        let statements = parse("def d0 as gas_sensor; def d5 as Heater; def db as Housing;");
+        let pins: Vec<(String, String)> = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Alias {
+                    identifier, alias, ..
+                } => (identifier.to_string(), alias.to_string()),
+                statement => panic!("expected an alias, found {statement:?}"),
+            })
+            .collect();
+        assert_eq!(
+            pins,
+            [
+                ("d0".to_string(), "gas_sensor".to_string()),
+                ("d5".to_string(), "Heater".to_string()),
+                ("db".to_string(), "Housing".to_string()),
+            ]
+        );
+
+        // names that only start like a pin are ordinary identifiers
+        let statements = parse_body(
+            "let device = 1; let d0x = 2; let dr0 = 3; let db_value = 4; let _d1 = 5; d0x.On = 1;",
+        );
+        let names: Vec<String> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Definition { identifier, .. } => Some(identifier.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, ["device", "d0x", "dr0", "db_value", "_d1"]);
+        assert!(matches!(
+            statements.last(),
+            Some(Statement::DeviceStatement(DeviceStatement::Write { device, .. })) if device.to_string() == "d0x"
+        ));
+
+        for source in [
+            "def device as x;",
+            "def d6 as x;",
+            "def dr0 as x;",
+            "def d0x as x;",
+            "let d0 = 1;",
+            "let 0d = 1;",
+        ] {
+            assert!(ProgramParser::new().parse(source).is_err(), "{source}");
+        }
+    }
+}
@@ -1,4 +1,101 @@
+use crate::{
+    ast::{Statement, Unit, Value},
+    error::{Error, Result},
+};
+use std::str::FromStr;
+
 pub fn append<T>(mut accum: Vec<T>, item: T) -> Vec<T> {
     accum.push(item);
     accum
 }
+
+/// A statement or a `///` comment, before the comments are attached to what they document.
+pub enum Item {
+    Statement(Statement),
+    DocComment(String),
+}
+
+/// Attaches `///` comments to the alias or function that directly follows them.
+/// Doc comments before anything else are ordinary comments and are dropped.
+pub fn attach_doc_comments(items: Vec<Item>) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut pending = Vec::new();
+    for item in items {
+        match item {
+            Item::DocComment(text) => pending.push(text),
+            Item::Statement(mut statement) => {
+                if let Statement::Alias { docs, .. } | Statement::Function { docs, .. } =
+                    &mut statement
+                {
+                    *docs = std::mem::take(&mut pending);
+                }
+                pending.clear();
+                statements.push(statement);
+            }
+        }
+    }
+
+    statements
+}
+
+/// Parses an integer literal, which may be hexadecimal (`0x`) or binary (`0b`) and may contain `_`
+/// separators.
+pub fn parse_integer(literal: &str) -> Result<i64> {
+    let digits = literal.replace('_', "");
+    let parsed = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        digits.parse()
+    };
+
+    parsed.map_err(|_| Error::NumberOutOfRange(literal.to_string()))
+}
+
+/// Parses a decimal number literal, which may contain an exponent and `_` separators.
+pub fn parse_float(literal: &str) -> Result<f64> {
+    match literal.replace('_', "").parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(Error::NumberOutOfRange(literal.to_string())),
+    }
+}
+
+/// Parses a number followed by a unit, e.g. `20C` or `101.325kPa`.
+pub fn parse_quantity(literal: &str) -> Result<Value> {
+    let split = literal
+        .find(|c: char| c.is_ascii_alphabetic())
+        .ok_or_else(|| Error::UnknownUnit(literal.to_string()))?;
+    let (value, unit) = literal.split_at(split);
+    let unit = Unit::from_str(unit).map_err(|_| Error::UnknownUnit(literal.to_string()))?;
+
+    Ok(Value::Quantity(parse_float(value)?, unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_literals() {
+        assert_eq!(parse_integer("1_000"), Ok(1000));
+        assert_eq!(parse_integer("0xFF_FF"), Ok(0xFFFF));
+        assert_eq!(parse_integer("0b1010"), Ok(10));
+        assert!(parse_integer("0x8000000000000000").is_err());
+        assert_eq!(parse_float("1.5e3"), Ok(1500.0));
+        assert_eq!(parse_float("2E-2"), Ok(0.02));
+        assert!(parse_float("1e400").is_err());
+        assert!(matches!(
+            parse_quantity("1_000.5kPa"),
+            Ok(Value::Quantity(value, Unit::KiloPascal)) if value == 1000.5
+        ));
+        assert_eq!(
+            parse_quantity("20").err(),
+            Some(Error::UnknownUnit("20".to_string()))
+        );
+        assert_eq!(
+            parse_quantity("20X").err(),
+            Some(Error::UnknownUnit("20X".to_string()))
+        );
+    }
+}